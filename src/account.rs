@@ -1,28 +1,134 @@
 use starknet::{
-    accounts::{Account, SingleOwnerAccount},
+    accounts::{Account, AccountFactory, ConnectedAccount, SingleOwnerAccount},
     core::{
         types::{
             contract::{legacy::LegacyContractClass, CompiledClass, SierraClass},
-            Call, DeclareTransactionResult, Felt,
+            BlockId, Call, DeclareTransactionResult, DeployAccountTransactionResult, Felt,
+            InvokeTransactionResult, StarknetError,
         },
-        utils::get_udc_deployed_address,
+        utils::get_contract_address,
     },
     macros::selector,
-    providers::jsonrpc::{HttpTransport, JsonRpcClient},
+    providers::{jsonrpc::JsonRpcClient, Provider, ProviderError},
     signers::LocalWallet,
 };
 use std::{error::Error, fs::File, sync::Arc};
 
+use crate::{
+    config::{TxSettings, TxVersion},
+    deploy::{confirm_deployment, is_deployed, DeployedAddress},
+    provider::FailoverTransport,
+    ws::WsClient,
+};
+
+/// Sends `calls` as a single invoke transaction of the configured version
+pub async fn execute(
+    account: &SingleOwnerAccount<&JsonRpcClient<FailoverTransport>, &LocalWallet>,
+    calls: Vec<Call>,
+    tx: TxSettings,
+) -> Result<InvokeTransactionResult, Box<dyn Error>> {
+    execute_with_nonce(account, calls, tx, None).await
+}
+
+/// Same as [`execute`] but with an explicit nonce, to send several transactions without waiting
+#[allow(deprecated)] // v1 stays the default for fee-free Madara devnets
+pub async fn execute_with_nonce(
+    account: &SingleOwnerAccount<&JsonRpcClient<FailoverTransport>, &LocalWallet>,
+    calls: Vec<Call>,
+    tx: TxSettings,
+    nonce: Option<Felt>,
+) -> Result<InvokeTransactionResult, Box<dyn Error>> {
+    let result = match tx.version {
+        TxVersion::V1 => {
+            let mut execution = account.execute_v1(calls);
+            if let Some(nonce) = nonce {
+                execution = execution.nonce(nonce);
+            }
+            if tx.no_fee() {
+                execution = execution.max_fee(Felt::ZERO);
+            }
+            execution.send().await?
+        }
+        TxVersion::V3 => {
+            let mut execution = account.execute_v3(calls);
+            if let Some(nonce) = nonce {
+                execution = execution.nonce(nonce);
+            }
+            if tx.no_fee() {
+                execution = execution.gas(0).gas_price(0);
+            }
+            execution.send().await?
+        }
+    };
+
+    Ok(result)
+}
+
+/// Deploys the account of the factory unless it is already deployed, and returns its address
+#[allow(deprecated)]
+pub async fn deploy_account<'a, F>(
+    factory: &F,
+    ws: Option<&WsClient>,
+    salt: Felt,
+    tx: TxSettings,
+) -> Result<Felt, Box<dyn Error>>
+where
+    F: AccountFactory<Provider = &'a JsonRpcClient<FailoverTransport>> + Sync,
+    F::SignError: 'static,
+{
+    let address = factory.deploy_v1(salt).address();
+    if is_deployed(
+        factory.provider(),
+        factory.block_id(),
+        address,
+        factory.class_hash(),
+    )
+    .await?
+    {
+        return Ok(address);
+    }
+
+    let DeployAccountTransactionResult {
+        transaction_hash, ..
+    } = match tx.version {
+        TxVersion::V1 => {
+            let mut deployment = factory.deploy_v1(salt).nonce(Felt::ZERO);
+            if tx.no_fee() {
+                deployment = deployment.max_fee(Felt::ZERO);
+            }
+            deployment.send().await?
+        }
+        TxVersion::V3 => {
+            let mut deployment = factory.deploy_v3(salt).nonce(Felt::ZERO);
+            if tx.no_fee() {
+                deployment = deployment.gas(0).gas_price(0);
+            }
+            deployment.send().await?
+        }
+    };
+    confirm_deployment(
+        factory.provider(),
+        ws,
+        transaction_hash,
+        address,
+        DeployedAddress::DeployAccount,
+    )
+    .await?;
+
+    Ok(address)
+}
+
 pub async fn declare_v1(
-    account: &SingleOwnerAccount<&JsonRpcClient<HttpTransport>, &LocalWallet>,
+    account: &SingleOwnerAccount<&JsonRpcClient<FailoverTransport>, &LocalWallet>,
     path: &str,
-    no_fee: bool,
+    tx: TxSettings,
 ) -> Result<DeclareTransactionResult, Box<dyn Error>> {
     let contract_artifact: LegacyContractClass = serde_json::from_reader(File::open(path)?)?;
 
+    // legacy classes can only be declared with a v1 transaction
     let mut declare = account.declare_legacy(Arc::new(contract_artifact));
 
-    if no_fee {
+    if tx.no_fee() {
         declare = declare.max_fee(Felt::ZERO);
     }
 
@@ -30,36 +136,125 @@ pub async fn declare_v1(
     Ok(result)
 }
 
+/// Declares the legacy class unless it is already declared, returns its hash and the declare
+/// transaction hash if one was sent
+pub async fn declare_v1_if_missing(
+    account: &SingleOwnerAccount<&JsonRpcClient<FailoverTransport>, &LocalWallet>,
+    path: &str,
+    tx: TxSettings,
+) -> Result<(Felt, Option<Felt>), Box<dyn Error>> {
+    let class_hash = legacy_class_hash(path)?;
+    if is_declared(account.provider(), account.block_id(), class_hash).await? {
+        return Ok((class_hash, None));
+    }
+    let result = declare_v1(account, path, tx).await?;
+    Ok((result.class_hash, Some(result.transaction_hash)))
+}
+
+/// Whether the class hash is declared on the node
+pub async fn is_declared<P: Provider + Sync>(
+    provider: &P,
+    block_id: BlockId,
+    class_hash: Felt,
+) -> Result<bool, Box<dyn Error>> {
+    match provider.get_class(block_id, class_hash).await {
+        Ok(_) => Ok(true),
+        Err(ProviderError::StarknetError(StarknetError::ClassHashNotFound)) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Class hash of a legacy (Cairo 0) class file
+pub fn legacy_class_hash(path: &str) -> Result<Felt, Box<dyn Error>> {
+    let contract_artifact: LegacyContractClass = serde_json::from_reader(File::open(path)?)?;
+    Ok(contract_artifact.class_hash()?)
+}
+
+/// Checks that the legacy class at `path`, deployed from zero with `salt` and
+/// `constructor_calldata`, lands on `expected`. Returns its class hash.
+pub fn check_legacy_address(
+    path: &str,
+    salt: Felt,
+    constructor_calldata: &[Felt],
+    expected: Felt,
+) -> Result<Felt, Box<dyn Error>> {
+    let class_hash =
+        legacy_class_hash(path).map_err(|e| format!("failed to read the class {path}: {e}"))?;
+    let address = get_contract_address(salt, class_hash, constructor_calldata, Felt::ZERO);
+    if address != expected {
+        return Err(format!(
+            "{path} would be deployed at 0x{:x} instead of 0x{:x}, check its salt",
+            address, expected
+        )
+        .into());
+    }
+    Ok(class_hash)
+}
+
+/// Class hash of a Sierra class file
+pub fn sierra_class_hash(path: &str) -> Result<Felt, Box<dyn Error>> {
+    let contract_artifact: SierraClass = serde_json::from_reader(File::open(path)?)?;
+    Ok(contract_artifact.flatten()?.class_hash())
+}
+
+/// Declares the class unless it is already declared, returns its hash and the declare
+/// transaction hash if one was sent
+pub async fn declare_v2_if_missing(
+    account: &SingleOwnerAccount<&JsonRpcClient<FailoverTransport>, &LocalWallet>,
+    path: &str,
+    compiled_path: &str,
+    tx: TxSettings,
+) -> Result<(Felt, Option<Felt>), Box<dyn Error>> {
+    let class_hash = sierra_class_hash(path)?;
+    if is_declared(account.provider(), account.block_id(), class_hash).await? {
+        return Ok((class_hash, None));
+    }
+    let result = declare_v2(account, path, compiled_path, tx).await?;
+    Ok((result.class_hash, Some(result.transaction_hash)))
+}
+
 pub async fn declare_v2(
-    account: &SingleOwnerAccount<&JsonRpcClient<HttpTransport>, &LocalWallet>,
+    account: &SingleOwnerAccount<&JsonRpcClient<FailoverTransport>, &LocalWallet>,
     path: &str,
     compiled_path: &str,
-    no_fee: bool,
+    tx: TxSettings,
 ) -> Result<DeclareTransactionResult, Box<dyn Error>> {
     let contract_artifact: SierraClass = serde_json::from_reader(File::open(path)?)?;
     let compiled_class: CompiledClass = serde_json::from_reader(File::open(compiled_path)?)?;
     let compiled_class_hash = compiled_class.class_hash()?;
     let flattened_class = contract_artifact.flatten()?;
 
-    let mut declare = account.declare_v2(Arc::new(flattened_class), compiled_class_hash);
-
-    if no_fee {
-        declare = declare.max_fee(Felt::ZERO);
-    }
+    let class = Arc::new(flattened_class);
 
-    let result = declare.send().await?;
+    #[allow(deprecated)]
+    let result = match tx.version {
+        TxVersion::V1 => {
+            let mut declare = account.declare_v2(class, compiled_class_hash);
+            if tx.no_fee() {
+                declare = declare.max_fee(Felt::ZERO);
+            }
+            declare.send().await?
+        }
+        TxVersion::V3 => {
+            let mut declare = account.declare_v3(class, compiled_class_hash);
+            if tx.no_fee() {
+                declare = declare.gas(0).gas_price(0);
+            }
+            declare.send().await?
+        }
+    };
 
     Ok(result)
 }
 
 pub async fn deploy(
-    account: &SingleOwnerAccount<&JsonRpcClient<HttpTransport>, &LocalWallet>,
-
+    account: &SingleOwnerAccount<&JsonRpcClient<FailoverTransport>, &LocalWallet>,
+    ws: Option<&WsClient>,
     class_hash: Felt,
     salt: Felt,
     constructor_calldata: &[Felt],
-    no_fee: bool,
-) -> Result<(Felt, Felt), Box<dyn Error>> {
+    tx: TxSettings,
+) -> Result<Felt, Box<dyn Error>> {
     let mut calldata = vec![
         class_hash,
         salt,
@@ -68,12 +263,18 @@ pub async fn deploy(
     ]; // deploy from zero
     calldata.extend_from_slice(constructor_calldata);
 
-    let contract_address = get_udc_deployed_address(
-        salt,
+    // deployed from zero, the address doesn't depend on the deployer account
+    let contract_address = get_contract_address(salt, class_hash, constructor_calldata, Felt::ZERO);
+    if is_deployed(
+        account.provider(),
+        account.block_id(),
+        contract_address,
         class_hash,
-        &starknet::core::utils::UdcUniqueness::NotUnique,
-        constructor_calldata,
-    );
+    )
+    .await?
+    {
+        return Ok(contract_address);
+    }
 
     let call = Call {
         to: account.address(),
@@ -81,13 +282,17 @@ pub async fn deploy(
         calldata,
     };
 
-    let mut deploy = account.execute_v1(vec![call]);
-
-    if no_fee {
-        deploy = deploy.max_fee(Felt::ZERO);
-    }
-
-    let result = deploy.send().await?;
+    let result = execute(account, vec![call], tx).await?;
+    confirm_deployment(
+        account.provider(),
+        ws,
+        result.transaction_hash,
+        contract_address,
+        DeployedAddress::DeployContract {
+            account: account.address(),
+        },
+    )
+    .await?;
 
-    Ok((result.transaction_hash, contract_address))
+    Ok(contract_address)
 }
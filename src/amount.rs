@@ -0,0 +1,257 @@
+use starknet::{
+    core::types::{BlockId, Felt, U256},
+    macros::selector,
+    providers::JsonRpcClient,
+};
+use std::{error::Error, fmt, str::FromStr};
+
+use crate::{provider::FailoverTransport, token::decimals, utils::call_string};
+
+/// Decimal digits of the largest U256
+const U256_MAX: &str =
+    "115792089237316195423570985008687907853269984665640564039457584007913129639935";
+
+/// A token amount as written by humans: `10`, `0.5 STRK` or `1e6`, converted to the raw
+/// value with the decimals of the token
+#[derive(Debug, Clone)]
+pub struct Amount {
+    raw: String,
+    /// Significant digits, the value is `digits * 10^exponent`
+    digits: String,
+    exponent: i64,
+    /// Symbol of the token, checked against the contract when given
+    symbol: Option<String>,
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+impl FromStr for Amount {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid amount `{value}`");
+
+        let mut parts = value.split_whitespace();
+        let number = parts.next().ok_or_else(invalid)?;
+        let symbol = parts.next().map(str::to_owned);
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+
+        let (mantissa, exponent) = match number.split_once(['e', 'E']) {
+            Some((mantissa, exponent)) => (mantissa, exponent.parse().map_err(|_| invalid())?),
+            None => (number, 0i64),
+        };
+        let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if integer.is_empty() && fraction.is_empty()
+            || !integer
+                .chars()
+                .chain(fraction.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            raw: value.trim().to_owned(),
+            digits: format!("{integer}{fraction}"),
+            exponent: exponent.saturating_sub(fraction.len() as i64),
+            symbol,
+        })
+    }
+}
+
+/// Parses a decimal integer, failing instead of overflowing
+fn parse_u256(digits: &str) -> Option<U256> {
+    let digits = digits.trim_start_matches('0');
+    if digits.len() > U256_MAX.len() || digits.len() == U256_MAX.len() && digits > U256_MAX {
+        return None;
+    }
+
+    Some(digits.bytes().fold(U256::from(0u8), |value, digit| {
+        value * U256::from(10u8) + U256::from(digit - b'0')
+    }))
+}
+
+impl Amount {
+    pub fn symbol(&self) -> Option<&str> {
+        self.symbol.as_deref()
+    }
+
+    /// Raw value for a token with `decimals` decimals, rejecting digits below the smallest
+    /// unit and values above U256
+    pub fn to_u256(&self, decimals: u8) -> Result<U256, String> {
+        let digits = self.digits.trim_start_matches('0');
+        let exponent = self.exponent.saturating_add(i64::from(decimals));
+
+        let digits = if digits.is_empty() {
+            String::new()
+        } else if exponent >= 0 {
+            if exponent > U256_MAX.len().saturating_sub(digits.len()) as i64 {
+                return Err(format!("amount {self} overflows a u256"));
+            }
+            format!("{digits}{}", "0".repeat(exponent as usize))
+        } else {
+            let dropped = digits
+                .len()
+                .saturating_sub(exponent.unsigned_abs() as usize);
+            if digits[dropped..].bytes().any(|digit| digit != b'0') {
+                return Err(format!(
+                    "amount {self} has more than {decimals} decimals, precision would be lost"
+                ));
+            }
+            digits[..dropped].to_owned()
+        };
+
+        parse_u256(&digits).ok_or_else(|| format!("amount {self} overflows a u256"))
+    }
+
+    /// Converts the amount with the decimals of the token, checking its symbol when given
+    pub async fn to_token_units(
+        &self,
+        provider: &JsonRpcClient<FailoverTransport>,
+        token: Felt,
+        block_id: BlockId,
+    ) -> Result<U256, Box<dyn Error>> {
+        if let Some(symbol) = &self.symbol {
+            let token_symbol = call_string(provider, token, selector!("symbol"), block_id).await?;
+            if !token_symbol.eq_ignore_ascii_case(symbol) {
+                return Err(format!(
+                    "amount {self} is in {symbol} but token 0x{:x} is {token_symbol}",
+                    token
+                )
+                .into());
+            }
+        }
+
+        let decimals = decimals(provider, token, block_id).await?;
+        Ok(self.to_u256(decimals)?)
+    }
+}
+
+/// `a + b`, `None` on overflow where the `Add` of `U256` panics
+pub fn checked_add(a: U256, b: U256) -> Option<U256> {
+    let (low, carry) = a.low().overflowing_add(b.low());
+    let high = a
+        .high()
+        .checked_add(b.high())?
+        .checked_add(u128::from(carry))?;
+    Some(U256::from_words(low, high))
+}
+
+/// Formats a raw value with the decimals of its token, e.g. `1500000000000000000` with 18
+/// decimals as `1.5`
+pub fn format_amount(value: U256, decimals: u8) -> String {
+    let digits = format!(
+        "{:0>width$}",
+        value.to_string(),
+        width = usize::from(decimals) + 1
+    );
+    let (integer, fraction) = digits.split_at(digits.len() - usize::from(decimals));
+    let fraction = fraction.trim_end_matches('0');
+
+    if fraction.is_empty() {
+        integer.to_owned()
+    } else {
+        format!("{integer}.{fraction}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(amount: &str, decimals: u8) -> Result<U256, String> {
+        amount.parse::<Amount>()?.to_u256(decimals)
+    }
+
+    #[test]
+    fn parses_human_amounts() {
+        assert_eq!(raw("10", 0).unwrap(), U256::from(10u8));
+        assert_eq!(
+            raw("10", 18).unwrap(),
+            U256::from(10_000_000_000_000_000_000u128)
+        );
+        assert_eq!(
+            raw("0.5 STRK", 18).unwrap(),
+            U256::from(500_000_000_000_000_000u128)
+        );
+        assert_eq!(raw("1e6", 0).unwrap(), U256::from(1_000_000u32));
+        assert_eq!(raw("1.5e-3", 6).unwrap(), U256::from(1_500u32));
+        assert_eq!(raw("0.000", 0).unwrap(), U256::from(0u8));
+    }
+
+    #[test]
+    fn keeps_the_symbol() {
+        let amount: Amount = "0.5 STRK".parse().unwrap();
+        assert_eq!(amount.symbol(), Some("STRK"));
+        assert_eq!(amount.to_string(), "0.5 STRK");
+        assert_eq!("1e6".parse::<Amount>().unwrap().symbol(), None);
+    }
+
+    #[test]
+    fn rejects_invalid_amounts() {
+        for amount in ["", ".", "1.2.3", "-1", "1e", "0x10", "1 STRK ETH", "1,5"] {
+            assert!(amount.parse::<Amount>().is_err(), "{amount}");
+        }
+    }
+
+    #[test]
+    fn rejects_too_many_decimals() {
+        assert!(raw("0.5", 0).unwrap_err().contains("decimals"));
+        assert!(raw("1.5e-3", 2).unwrap_err().contains("decimals"));
+        assert_eq!(raw("1.50", 1).unwrap(), U256::from(15u8));
+    }
+
+    #[test]
+    fn rejects_values_above_u256() {
+        let max = U256::from_words(u128::MAX, u128::MAX);
+        assert_eq!(raw(U256_MAX, 0).unwrap(), max);
+        assert!(raw(
+            "115792089237316195423570985008687907853269984665640564039457584007913129639936",
+            0
+        )
+        .unwrap_err()
+        .contains("overflows"));
+        assert!(raw(U256_MAX, 1).unwrap_err().contains("overflows"));
+        assert!(raw("1e78", 0).unwrap_err().contains("overflows"));
+    }
+
+    #[test]
+    fn checked_add_carries_and_overflows() {
+        let max = U256::from_words(u128::MAX, u128::MAX);
+        assert_eq!(
+            checked_add(U256::from_words(u128::MAX, 0), U256::from(1u8)),
+            Some(U256::from_words(0, 1))
+        );
+        assert_eq!(checked_add(max, U256::from(0u8)), Some(max));
+        assert_eq!(checked_add(max, U256::from(1u8)), None);
+        assert_eq!(
+            checked_add(U256::from_words(0, u128::MAX), U256::from_words(0, 1)),
+            None
+        );
+    }
+
+    #[test]
+    fn format_amount_round_trips() {
+        for (value, decimals, formatted) in [
+            (U256::from(1_500_000_000_000_000_000u128), 18, "1.5"),
+            (U256::from(10u8), 0, "10"),
+            (U256::from(1u8), 6, "0.000001"),
+            (U256::from(0u8), 18, "0"),
+            (
+                U256::from_words(u128::MAX, u128::MAX),
+                18,
+                "115792089237316195423570985008687907853269984665640564039457.584007913129639935",
+            ),
+        ] {
+            let text = format_amount(value, decimals);
+            assert_eq!(text, formatted);
+            assert_eq!(raw(&text, decimals).unwrap(), value, "{text}");
+        }
+    }
+}
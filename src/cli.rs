@@ -0,0 +1,201 @@
+use clap::{Args, Parser, Subcommand};
+use starknet::core::types::Felt;
+use std::path::PathBuf;
+
+use crate::{
+    amount::Amount,
+    config::{BlockTagConfig, FeePolicy, SettlementLayer, TxVersion, UdcUniqueness},
+};
+
+/// Bootstraps a Madara L3 and deploys its core contract on the settlement chain
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(flatten)]
+    pub config: ConfigArgs,
+    /// Bootstraps the L3 when no command is given
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Computes a storage address, e.g. `ERC20_balances[0x123]`, to write storage migrations
+    Storage(StorageArgs),
+    /// Upgrades an OpenZeppelin upgradeable contract of the L3 (Token, Account) to a new class
+    Upgrade(UpgradeArgs),
+    /// Runs an HTTP faucet sending STRK and QUAZA from the account of the L3
+    Faucet(FaucetArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct StorageArgs {
+    /// Variable name followed by `[key]` for map entries (comma separated for multi-felt keys
+    /// such as u256) and `.member` for storage nodes and non-flattened substorage
+    pub path: String,
+    /// Prints the storage writes of this string stored as a ByteArray at the path
+    #[arg(long)]
+    pub byte_array: Option<String>,
+    /// Reads the current values of the keys on this contract of the L3
+    #[arg(long)]
+    pub contract: Option<Felt>,
+}
+
+#[derive(Debug, Args)]
+pub struct UpgradeArgs {
+    /// Contract to upgrade, defaults to the account itself which must upgrade through `self`
+    #[arg(long)]
+    pub contract: Option<Felt>,
+    /// Sierra class of the new implementation, declared if missing
+    #[arg(long, requires = "compiled_class", conflicts_with = "class_hash")]
+    pub class: Option<String>,
+    /// CASM class of the new implementation
+    #[arg(long)]
+    pub compiled_class: Option<String>,
+    /// Class hash of an already declared implementation
+    #[arg(long, required_unless_present = "class")]
+    pub class_hash: Option<Felt>,
+    /// Function of the new class called right after the upgrade, through `upgrade_and_call`
+    #[arg(long)]
+    pub call: Option<String>,
+    /// Comma separated calldata of the function, as hex felts or short strings
+    #[arg(long, value_delimiter = ',', requires = "call")]
+    pub calldata: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct FaucetArgs {
+    /// Address the HTTP server listens on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    pub listen: String,
+    /// Address of the STRK token
+    #[arg(long, required_unless_present = "quaza")]
+    pub strk: Option<Felt>,
+    /// Address of the QUAZA token
+    #[arg(long)]
+    pub quaza: Option<Felt>,
+    /// STRK sent per request
+    #[arg(long, default_value = "10 STRK")]
+    pub strk_amount: Amount,
+    /// QUAZA sent per request
+    #[arg(long, default_value = "10 QUAZA")]
+    pub quaza_amount: Amount,
+    /// Seconds before an address can be funded again
+    #[arg(long, default_value_t = 86400)]
+    pub address_cooldown: u64,
+    /// Requests accepted from one IP per window
+    #[arg(long, default_value_t = 10)]
+    pub ip_limit: usize,
+    /// Seconds of the per-IP window
+    #[arg(long, default_value_t = 3600)]
+    pub ip_window: u64,
+}
+
+/// Flags overriding the config file and the environment
+#[derive(Debug, Args)]
+pub struct ConfigArgs {
+    /// Path to the TOML config file [default: quaza.toml if present]
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+    /// Profile of the L3 to bootstrap
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+    /// Profile of the settlement chain, defaults to the `settlement` field of the L3 profile
+    #[arg(long, global = true)]
+    pub settlement_profile: Option<String>,
+
+    /// Allow sending transactions to Starknet mainnet
+    #[arg(long, global = true)]
+    pub allow_mainnet: bool,
+    /// Seconds to wait for the L3 node to be ready [default: 120]
+    #[arg(long, global = true)]
+    pub ready_timeout: Option<u64>,
+    /// Storage migration file (TOML or JSON) applied by the EIC when upgrading STRK
+    /// [default: migrations/strk.toml]
+    #[arg(long, global = true)]
+    pub strk_migration: Option<PathBuf>,
+    /// Salt of the original ETH deployment on Starknet, ETH is only deployed when set
+    #[arg(long, global = true)]
+    pub eth_salt: Option<String>,
+    /// Storage migration file applied by the EIC when upgrading ETH
+    /// [default: migrations/eth.toml]
+    #[arg(long, global = true)]
+    pub eth_migration: Option<PathBuf>,
+    /// Namespace the salts of the deployed contracts are derived from
+    #[arg(long, global = true)]
+    pub salt_namespace: Option<String>,
+
+    /// Expected chain id of the L3, as a short string or a hex felt
+    #[arg(long, global = true)]
+    pub chain_id: Option<String>,
+    /// Comma separated list of RPC endpoints of the L3
+    #[arg(long, global = true)]
+    pub rpc_url: Option<String>,
+    #[arg(long, global = true)]
+    pub rpc_admin_url: Option<String>,
+    /// WebSocket endpoint of the L3 for transaction status subscriptions
+    #[arg(long, global = true)]
+    pub ws_url: Option<String>,
+    #[arg(long, global = true, value_enum)]
+    pub fee_policy: Option<FeePolicy>,
+    #[arg(long, global = true, value_enum)]
+    pub tx_version: Option<TxVersion>,
+    #[arg(long, global = true, value_enum)]
+    pub block_tag: Option<BlockTagConfig>,
+    /// Name of a signer declared in the config file
+    #[arg(long, global = true)]
+    pub signer: Option<String>,
+    /// Account of the L3 used by the commands, the bootstrap deploys its own
+    #[arg(long, global = true)]
+    pub account_address: Option<String>,
+    /// Address receiving the ownership of STRK and QUAZA once bootstrapped
+    #[arg(long, global = true)]
+    pub governance_address: Option<String>,
+    /// Genesis allocation file (CSV or JSON), 10 STRK and 10 QUAZA go to the deployer without it
+    #[arg(long, global = true)]
+    pub genesis: Option<String>,
+    /// UDC of the L3 [default: the Starknet UDC address]
+    #[arg(long, global = true)]
+    pub udc_address: Option<String>,
+    /// Whether deployed addresses depend on the deployer [default: not-unique]
+    #[arg(long, global = true, value_enum)]
+    pub udc_uniqueness: Option<UdcUniqueness>,
+
+    /// Expected chain id of the settlement chain, as a short string or a hex felt
+    #[arg(long, global = true)]
+    pub settlement_chain_id: Option<String>,
+    /// Comma separated list of RPC endpoints of the settlement chain, Starknet or Ethereum
+    #[arg(long, global = true, alias = "rpc-starknet-url")]
+    pub settlement_rpc_url: Option<String>,
+    /// WebSocket endpoint of the settlement chain for transaction status subscriptions
+    #[arg(long, global = true)]
+    pub settlement_ws_url: Option<String>,
+    #[arg(long, global = true)]
+    pub starknet_account_address: Option<String>,
+    #[arg(long, global = true, value_enum)]
+    pub settlement_fee_policy: Option<FeePolicy>,
+    #[arg(long, global = true, value_enum)]
+    pub settlement_tx_version: Option<TxVersion>,
+    #[arg(long, global = true, value_enum)]
+    pub settlement_block_tag: Option<BlockTagConfig>,
+    #[arg(long, global = true)]
+    pub settlement_signer: Option<String>,
+    /// UDC of the settlement chain [default: the Starknet UDC address]
+    #[arg(long, global = true)]
+    pub settlement_udc_address: Option<String>,
+    #[arg(long, global = true, value_enum)]
+    pub settlement_udc_uniqueness: Option<UdcUniqueness>,
+    /// Kind of settlement chain [default: starknet]
+    #[arg(long, global = true, value_enum)]
+    pub settlement_layer: Option<SettlementLayer>,
+
+    /// Class hash of the core contract, declared on the settlement chain
+    #[arg(long, global = true)]
+    pub core_contract_class_hash: Option<String>,
+    /// Owner of the core contract once initialized [default: the settlement account]
+    #[arg(long, global = true)]
+    pub core_contract_owner: Option<String>,
+    /// L3 block whose state the core contract starts from [default: 0]
+    #[arg(long, global = true)]
+    pub core_contract_block_number: Option<u64>,
+}
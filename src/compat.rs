@@ -0,0 +1,216 @@
+use serde_json::{json, Value};
+use starknet::providers::jsonrpc::JsonRpcMethod;
+use std::fmt;
+
+use crate::config::TxVersion;
+
+/// RPC spec versions the tool can talk to.
+///
+/// The starknet-rs types are those of 0.7, newer nodes are adapted by rewriting requests and
+/// responses in the transport. 0.8 changed the v3 resource bounds, and therefore the
+/// transaction hash, which the signer can't produce, so it only takes v1 transactions. 0.9 only
+/// accepts those v3 transactions and can only be read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RpcSpec {
+    V0_7,
+    V0_8,
+    V0_9,
+}
+
+impl fmt::Display for RpcSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::V0_7 => write!(f, "0.7"),
+            Self::V0_8 => write!(f, "0.8"),
+            Self::V0_9 => write!(f, "0.9"),
+        }
+    }
+}
+
+impl RpcSpec {
+    pub const SUPPORTED: &'static [RpcSpec] = &[Self::V0_7, Self::V0_8, Self::V0_9];
+
+    /// Parses a `starknet_specVersion` answer such as `0.7.1`
+    pub fn parse(version: &str) -> Result<Self, String> {
+        let mut parts = version.split('.');
+        match (parts.next(), parts.next()) {
+            (Some("0"), Some("7")) => Ok(Self::V0_7),
+            (Some("0"), Some("8")) => Ok(Self::V0_8),
+            (Some("0"), Some("9")) => Ok(Self::V0_9),
+            _ => Err(format!(
+                "unsupported RPC spec version {version}, supported: {}",
+                Self::SUPPORTED
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+
+    /// Whether transactions of `version` can be sent to the node
+    pub fn supports_writes(self, version: TxVersion) -> bool {
+        match self {
+            Self::V0_7 => true,
+            Self::V0_8 => version == TxVersion::V1,
+            Self::V0_9 => false,
+        }
+    }
+
+    /// Adapts the params of a request built with the 0.7 types
+    pub fn adapt_request(self, params: &mut Value) {
+        // 0.9 replaced the pending block by the pre-confirmed one
+        if self >= Self::V0_9 {
+            if let Some(block_id) = params.get_mut("block_id") {
+                if block_id == "pending" {
+                    *block_id = json!("pre_confirmed");
+                }
+            }
+        }
+    }
+
+    /// Adapts the result of a response to the shape of the 0.7 types
+    pub fn adapt_response(self, method: JsonRpcMethod, result: &mut Value) {
+        if self == Self::V0_7 {
+            return;
+        }
+
+        match method {
+            JsonRpcMethod::GetTransactionStatus => adapt_transaction_status(result),
+            JsonRpcMethod::GetTransactionReceipt => adapt_receipt(result),
+            JsonRpcMethod::GetBlockWithReceipts => {
+                if let Some(transactions) =
+                    result.get_mut("transactions").and_then(Value::as_array_mut)
+                {
+                    for transaction in transactions {
+                        if let Some(receipt) = transaction.get_mut("receipt") {
+                            adapt_receipt(receipt);
+                        }
+                    }
+                }
+            }
+            JsonRpcMethod::EstimateFee => {
+                if let Some(estimates) = result.as_array_mut() {
+                    estimates.iter_mut().for_each(adapt_fee_estimate);
+                }
+            }
+            JsonRpcMethod::EstimateMessageFee => adapt_fee_estimate(result),
+            _ => {}
+        }
+    }
+}
+
+/// Version of the transaction sent by a write request, v3 or a legacy one
+pub fn transaction_version(params: &Value) -> TxVersion {
+    let version = params
+        .as_object()
+        .and_then(|params| params.values().next())
+        .and_then(|transaction| transaction.get("version"))
+        .and_then(Value::as_str);
+    match version {
+        Some("0x3") => TxVersion::V3,
+        _ => TxVersion::V1,
+    }
+}
+
+/// 0.9 added the `PRE_CONFIRMED` and `CANDIDATE` statuses, both are not final yet
+fn adapt_transaction_status(status: &mut Value) {
+    let Some(status) = status.as_object_mut() else {
+        return;
+    };
+    if matches!(
+        status.get("finality_status").and_then(Value::as_str),
+        Some("PRE_CONFIRMED" | "CANDIDATE")
+    ) {
+        status.insert("finality_status".into(), json!("RECEIVED"));
+        status.remove("execution_status");
+    }
+}
+
+fn adapt_receipt(receipt: &mut Value) {
+    let Some(receipt) = receipt.as_object_mut() else {
+        return;
+    };
+
+    // a pre-confirmed transaction is executed, only its block isn't closed yet
+    if receipt.get("finality_status").and_then(Value::as_str) == Some("PRE_CONFIRMED") {
+        receipt.insert("finality_status".into(), json!("ACCEPTED_ON_L2"));
+    }
+
+    // 0.8 replaced the VM resources by the gas consumed per resource
+    if let Some(resources) = receipt
+        .get_mut("execution_resources")
+        .and_then(Value::as_object_mut)
+    {
+        if !resources.contains_key("steps") {
+            let l1_gas = resources.get("l1_gas").cloned().unwrap_or(json!(0));
+            let l1_data_gas = resources.get("l1_data_gas").cloned().unwrap_or(json!(0));
+            resources.insert("steps".into(), json!(0));
+            resources.insert(
+                "data_availability".into(),
+                json!({ "l1_gas": l1_gas, "l1_data_gas": l1_data_gas }),
+            );
+        }
+    }
+}
+
+/// 0.8 split the estimate per gas kind
+fn adapt_fee_estimate(estimate: &mut Value) {
+    let Some(estimate) = estimate.as_object_mut() else {
+        return;
+    };
+
+    for (from, to) in [
+        ("l1_gas_consumed", "gas_consumed"),
+        ("l1_gas_price", "gas_price"),
+        ("l1_data_gas_consumed", "data_gas_consumed"),
+        ("l1_data_gas_price", "data_gas_price"),
+    ] {
+        if let Some(value) = estimate.get(from).cloned() {
+            estimate.entry(to).or_insert(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_legacy_transactions_are_sent_over_0_8() {
+        let invoke = |version: &str| json!({ "invoke_transaction": { "version": version } });
+        assert_eq!(transaction_version(&invoke("0x1")), TxVersion::V1);
+        assert_eq!(transaction_version(&invoke("0x3")), TxVersion::V3);
+
+        assert!(RpcSpec::V0_7.supports_writes(TxVersion::V3));
+        assert!(RpcSpec::V0_8.supports_writes(TxVersion::V1));
+        assert!(!RpcSpec::V0_8.supports_writes(TxVersion::V3));
+        assert!(!RpcSpec::V0_9.supports_writes(TxVersion::V1));
+    }
+
+    #[test]
+    fn responses_are_adapted_to_0_7() {
+        let mut status =
+            json!({ "finality_status": "PRE_CONFIRMED", "execution_status": "SUCCEEDED" });
+        RpcSpec::V0_9.adapt_response(JsonRpcMethod::GetTransactionStatus, &mut status);
+        assert_eq!(status, json!({ "finality_status": "RECEIVED" }));
+
+        let mut receipt = json!({
+            "finality_status": "PRE_CONFIRMED",
+            "execution_resources": { "l1_gas": 1, "l1_data_gas": 2, "l2_gas": 3 },
+        });
+        RpcSpec::V0_9.adapt_response(JsonRpcMethod::GetTransactionReceipt, &mut receipt);
+        assert_eq!(receipt["finality_status"], "ACCEPTED_ON_L2");
+        assert_eq!(receipt["execution_resources"]["steps"], 0);
+        assert_eq!(
+            receipt["execution_resources"]["data_availability"],
+            json!({ "l1_gas": 1, "l1_data_gas": 2 })
+        );
+
+        let mut estimates =
+            json!([{ "l1_gas_consumed": "0x1", "l1_gas_price": "0x2", "overall_fee": "0x2" }]);
+        RpcSpec::V0_8.adapt_response(JsonRpcMethod::EstimateFee, &mut estimates);
+        assert_eq!(estimates[0]["gas_consumed"], "0x1");
+        assert_eq!(estimates[0]["gas_price"], "0x2");
+    }
+}
@@ -1,35 +1,1206 @@
+use alloy::primitives::B256;
+use clap::ValueEnum;
 use dotenv::dotenv;
-use serde::Deserialize;
-use starknet::core::types::Felt;
-use std::env;
+use serde::{Deserialize, Deserializer};
+use starknet::{
+    core::{
+        types::{BlockId, BlockTag, Felt},
+        utils::{self, cairo_short_string_to_felt, get_selector_from_name},
+    },
+    providers::Url,
+};
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    cli::ConfigArgs,
+    provider::{Auth, Endpoint, RetryPolicy},
+};
 
 pub const STRK_SALT: Felt =
     Felt::from_hex_unchecked("0x048a38cb46716a7cc3a7b5132309388f298bc49c53f5de377bb5401d877b7f89");
 
-#[derive(Debug, Deserialize)]
+/// Address of the ETH fee token on Starknet, where the ETH proxy must be deployed
+pub const ETH_ADDRESS: Felt =
+    Felt::from_hex_unchecked("0x049d36570d4e46f48e99674bd3fcc84644ddd6b96f7c741b1562b82f9e004dc7");
+
+/// Address of the UDC on Starknet, assumed by wallets, starknet.js and `ContractFactory`
+pub const UDC_ADDRESS: Felt =
+    Felt::from_hex_unchecked("0x041a78e741e5af2fec34b695679bc6891742439f7afb8484ecd7766661ad02bf");
+/// Class of the Starknet UDC, deployed from zero with a zero salt and no calldata
+pub const UDC_CLASS_HASH: Felt =
+    Felt::from_hex_unchecked("0x07b3e05f48f0c69e4a65ce5e076a66271a527aff2c34ce1083ec6e1526997a69");
+
+/// Class of the core contract declared on Starknet Sepolia
+pub const DEFAULT_CORE_CONTRACT_CLASS_HASH: Felt =
+    Felt::from_hex_unchecked("0x07e32e97ad7d1809358418ec553d61d0f537fba13d5b8ac3aa479ec9c632ef95");
+
+pub const DEFAULT_CONFIG_PATH: &str = "quaza.toml";
+
+/// How long to wait for the L3 node to be ready before giving up
+pub const DEFAULT_READY_TIMEOUT: Duration = Duration::from_secs(120);
+pub const DEFAULT_STRK_MIGRATION_PATH: &str = "migrations/strk.toml";
+/// Foundry or Hardhat artifacts of the Solidity core contract and its proxy
+pub const DEFAULT_ETHEREUM_CORE_CONTRACT_PATH: &str = "contracts/ethereum/Starknet.json";
+pub const DEFAULT_ETHEREUM_PROXY_PATH: &str = "contracts/ethereum/Proxy.json";
+pub const DEFAULT_ETH_MIGRATION_PATH: &str = "migrations/eth.toml";
+
+/// Whether transactions are sent with a zero fee (Madara devnets) or with an estimated one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum FeePolicy {
+    Free,
+    Estimate,
+}
+
+/// Chain hosting the core contract of the L3
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SettlementLayer {
+    Starknet,
+    Ethereum,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum TxVersion {
+    V1,
+    V3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockTagConfig {
+    Pending,
+    Latest,
+}
+
+impl From<BlockTagConfig> for BlockId {
+    fn from(tag: BlockTagConfig) -> Self {
+        match tag {
+            BlockTagConfig::Pending => BlockId::Tag(BlockTag::Pending),
+            BlockTagConfig::Latest => BlockId::Tag(BlockTag::Latest),
+        }
+    }
+}
+
+/// How transactions are built and paid for on a given network
+#[derive(Debug, Clone, Copy)]
+pub struct TxSettings {
+    pub version: TxVersion,
+    pub fee_policy: FeePolicy,
+}
+
+impl TxSettings {
+    pub fn no_fee(&self) -> bool {
+        self.fee_policy == FeePolicy::Free
+    }
+}
+
+/// Whether the address of a contract deployed through the UDC depends on the deployer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum UdcUniqueness {
+    Unique,
+    NotUnique,
+}
+
+/// The UDC contracts are deployed through on a given network
+#[derive(Debug, Clone, Copy)]
+pub struct UdcSettings {
+    pub address: Felt,
+    pub uniqueness: UdcUniqueness,
+}
+
+impl UdcSettings {
+    pub fn unique(&self) -> bool {
+        self.uniqueness == UdcUniqueness::Unique
+    }
+
+    /// Address of a contract deployed through the UDC by `deployer`
+    pub fn deployed_address(
+        &self,
+        deployer: Felt,
+        salt: Felt,
+        class_hash: Felt,
+        constructor_calldata: &[Felt],
+    ) -> Felt {
+        let uniqueness = match self.uniqueness {
+            UdcUniqueness::Unique => utils::UdcUniqueness::Unique(utils::UdcUniqueSettings {
+                deployer_address: deployer,
+                udc_contract_address: self.address,
+            }),
+            UdcUniqueness::NotUnique => utils::UdcUniqueness::NotUnique,
+        };
+        utils::get_udc_deployed_address(salt, class_hash, &uniqueness, constructor_calldata)
+    }
+}
+
+/// Salts of the contracts deployed by the bootstrap, looked up by name: an explicit salt from
+/// the `[salts]` table, then one derived from the namespace, then the default of the contract
+#[derive(Debug, Default)]
+pub struct Salts {
+    namespace: Option<String>,
+    overrides: HashMap<String, Felt>,
+}
+
+impl Salts {
+    /// Contracts deployed with a configurable salt, the UDC, STRK and ETH keep their Starknet ones
+    const NAMES: [&str; 5] = [
+        "deployer_account",
+        "account",
+        "quaza",
+        "counter",
+        "core_contract",
+    ];
+
+    pub fn get(&self, name: &str, default: Felt) -> Felt {
+        if let Some(salt) = self.overrides.get(name) {
+            return *salt;
+        }
+        match &self.namespace {
+            Some(namespace) => utils::starknet_keccak(format!("{namespace}.{name}").as_bytes()),
+            None => default,
+        }
+    }
+}
+
+/// A signer declared in the `[signers.<name>]` section of the config file.
+/// The secret key is either inlined or read from the named environment variable.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SignerConfig {
+    pub secret_key: Option<String>,
+    pub secret_key_env: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+pub enum AuthConfig {
+    Bearer(String),
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+}
+
+/// An endpoint given either as a bare URL or as a table with its headers and credentials.
+/// Header values and credentials may reference env vars as `${NAME}`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum EndpointConfig {
+    Url(String),
+    Detailed {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+        auth: Option<AuthConfig>,
+    },
+}
+
+/// A named network profile. Every field is optional so that env vars and CLI flags can fill the gaps.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Expected chain id, either as a short string (`SN_SEPOLIA`) or as a hex felt
+    pub chain_id: Option<String>,
+    /// One endpoint or a list of endpoints of the same chain, tried in order
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub rpc_url: Option<Vec<EndpointConfig>>,
+    /// Madara admin endpoint, only needed on the L3
+    pub rpc_admin_url: Option<EndpointConfig>,
+    /// WebSocket endpoint (RPC 0.8+) for status subscriptions, polling is used without it
+    pub ws_url: Option<EndpointConfig>,
+    /// Existing account used to send transactions, the L3 only needs one for the commands
+    pub account_address: Option<String>,
+    /// Owner of the tokens once bootstrapped, the deployer keeps them when unset
+    pub governance_address: Option<String>,
+    /// Genesis allocation file (CSV or JSON) minted or transferred by the bootstrap
+    pub genesis: Option<String>,
+
+    pub fee_policy: Option<FeePolicy>,
+    pub tx_version: Option<TxVersion>,
+    pub block_tag: Option<BlockTagConfig>,
+    pub signer: Option<String>,
+    /// UDC used for deployments, the Starknet one by default
+    pub udc_address: Option<String>,
+    pub udc_uniqueness: Option<UdcUniqueness>,
+
+    /// Retries of a failed RPC request, across all endpoints
+    pub max_retries: Option<u32>,
+    /// Delay before the first retry, doubled on each retry
+    pub retry_base_delay_ms: Option<u64>,
+
+    /// Name of the profile used as settlement chain when this one is selected as the L3
+    pub settlement: Option<String>,
+    /// Kind of chain of a settlement profile, Starknet by default
+    pub layer: Option<SettlementLayer>,
+}
+
+impl Profile {
+    /// Overrides the fields of `self` with the ones set in `other`
+    fn merge(&mut self, other: Profile) {
+        macro_rules! merge_fields {
+            ($($field:ident),*) => {
+                $(if other.$field.is_some() {
+                    self.$field = other.$field;
+                })*
+            };
+        }
+
+        merge_fields!(
+            chain_id,
+            rpc_url,
+            rpc_admin_url,
+            ws_url,
+            account_address,
+            governance_address,
+            genesis,
+            fee_policy,
+            tx_version,
+            block_tag,
+            signer,
+            udc_address,
+            udc_uniqueness,
+            max_retries,
+            retry_base_delay_ms,
+            settlement,
+            layer
+        );
+    }
+}
+
+/// A call sent to the core contract once deployed, e.g. `set_facts_registry`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CallConfig {
+    pub function: String,
+    /// Hex felts, short strings or `${NAME}` env var references
+    #[serde(default)]
+    pub calldata: Vec<String>,
+}
+
+/// The `[core_contract]` section of the config file
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CoreContractFile {
+    /// Class already declared on the settlement chain
+    pub class_hash: Option<String>,
+    /// Sierra and CASM files of the class, declared if missing, instead of `class_hash`
+    pub class: Option<String>,
+    pub compiled_class: Option<String>,
+    /// Owner once initialized, the settlement account by default
+    pub owner: Option<String>,
+    /// L3 block whose state the core contract starts from
+    pub block_number: Option<u64>,
+    /// Sent with `set_program_info(program_hash, config_hash)` when both are set
+    pub program_hash: Option<String>,
+    pub config_hash: Option<String>,
+    /// Registered with `register_operator`
+    #[serde(default)]
+    pub operators: Vec<String>,
+    /// Other initialization calls, sent after the ones above, Starknet only
+    #[serde(default)]
+    pub calls: Vec<CallConfig>,
+    #[serde(default)]
+    pub ethereum: EthereumCoreContractFile,
+}
+
+/// The `[core_contract.ethereum]` section, used when the settlement layer is Ethereum
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EthereumCoreContractFile {
+    /// Artifacts of `Starknet.sol` and `Proxy.sol`
+    pub core_contract_artifact: Option<PathBuf>,
+    pub proxy_artifact: Option<PathBuf>,
+    /// Verifier of the state updates, must be a contract
+    pub verifier: Option<String>,
+    /// Only for core contract versions taking it in their initialization data
+    pub aggregator_program_hash: Option<String>,
+    /// Seconds before a new implementation of the proxy can be activated
+    pub upgrade_delay: Option<u64>,
+}
+
+/// Class of the core contract
+#[derive(Debug)]
+pub enum CoreContractClass {
+    Declared(Felt),
+    Artifact {
+        class: String,
+        compiled_class: String,
+    },
+}
+
+#[derive(Debug)]
+pub struct EthereumCoreContractConfig {
+    pub core_contract_artifact: PathBuf,
+    pub proxy_artifact: PathBuf,
+    pub verifier: Option<Felt>,
+    pub aggregator_program_hash: Option<Felt>,
+    pub upgrade_delay: u64,
+}
+
+#[derive(Debug)]
+pub struct CoreContractConfig {
+    pub class: CoreContractClass,
+    pub owner: Option<Felt>,
+    pub block_number: u64,
+    /// `(program_hash, config_hash)`
+    pub program_info: Option<(Felt, Felt)>,
+    pub operators: Vec<Felt>,
+    /// Other initialization calls as `(selector, calldata)`
+    pub calls: Vec<(Felt, Vec<Felt>)>,
+    pub ethereum: EthereumCoreContractConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    pub default_profile: Option<String>,
+    /// Seconds to wait for the L3 node to be ready
+    pub ready_timeout: Option<u64>,
+    /// Storage migration applied by the EIC when upgrading STRK
+    pub strk_migration: Option<PathBuf>,
+    /// Salt of the original ETH deployment, the ETH fee token is only deployed when set
+    pub eth_salt: Option<String>,
+    /// Storage migration applied by the EIC when upgrading ETH
+    pub eth_migration: Option<PathBuf>,
+    /// Salts are derived from this namespace, so that a new namespace gives new addresses
+    pub salt_namespace: Option<String>,
+    /// Salt of each deployed contract, by name
+    #[serde(default)]
+    pub salts: HashMap<String, String>,
+    #[serde(default)]
+    pub core_contract: CoreContractFile,
+    #[serde(default)]
+    pub signers: HashMap<String, SignerConfig>,
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+}
+
+/// Fully resolved settings of one network
+#[derive(Debug)]
+pub struct NetworkConfig {
+    pub profile: Option<String>,
+    pub chain_id: Felt,
+    pub rpc_endpoints: Vec<Endpoint>,
+    pub retry: RetryPolicy,
+    pub rpc_admin: Option<Endpoint>,
+    pub ws: Option<Endpoint>,
+    pub account_address: Option<Felt>,
+    pub governance_address: Option<Felt>,
+    pub genesis: Option<PathBuf>,
+    pub secret_key: Felt,
+    pub tx: TxSettings,
+    pub block_id: BlockId,
+    pub udc: UdcSettings,
+}
+
+/// Resolved settings of an Ethereum settlement chain
+#[derive(Debug)]
+pub struct EthereumConfig {
+    pub chain_id: u64,
+    pub rpc_url: Url,
+    pub secret_key: B256,
+}
+
+/// The network hosting the core contract
+#[derive(Debug)]
+pub enum Settlement {
+    Starknet(Box<NetworkConfig>),
+    Ethereum(EthereumConfig),
+}
+
+/// Settings of the commands that only talk to the L3
+#[derive(Debug)]
+pub struct L3Config {
+    pub l3: NetworkConfig,
+    /// Whether transactions may be sent to Starknet mainnet
+    pub allow_mainnet: bool,
+}
+
+#[derive(Debug)]
 pub struct Config {
-    pub rpc_url: String,
-    pub rpc_admin_url: String,
-    pub rpc_starknet_url: String,
+    /// The Madara chain being bootstrapped
+    pub l3: NetworkConfig,
+    pub settlement: Settlement,
+    /// Whether transactions may be sent to Starknet mainnet
+    pub allow_mainnet: bool,
+    pub ready_timeout: Duration,
+    pub strk_migration: PathBuf,
+    pub eth_salt: Option<Felt>,
+    pub eth_migration: PathBuf,
+    pub salts: Salts,
+    pub core_contract: CoreContractConfig,
+}
+
+/// Accepts either a single endpoint or a list of endpoints
+fn one_or_many<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<EndpointConfig>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(EndpointConfig),
+        Many(Vec<EndpointConfig>),
+    }
+
+    Ok(
+        Option::<OneOrMany>::deserialize(deserializer)?.map(|value| match value {
+            OneOrMany::One(endpoint) => vec![endpoint],
+            OneOrMany::Many(endpoints) => endpoints,
+        }),
+    )
+}
 
-    pub deployer_secret_key: Felt,
-    pub starknet_account_address: Felt,
+/// Splits a comma separated list of URLs as accepted by env vars and CLI flags
+fn split_urls(urls: &str) -> Vec<EndpointConfig> {
+    urls.split(',')
+        .map(|url| EndpointConfig::Url(url.trim().to_owned()))
+        .collect()
 }
 
-pub fn load_config() -> Config {
+/// Replaces the `${NAME}` references of `value` with the content of the env var
+pub fn expand_env(value: &str) -> Result<String, Box<dyn Error>> {
+    let mut expanded = String::new();
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unterminated env var reference in `{value}`"))?;
+        let name = &rest[start + 2..start + end];
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(&env::var(name).map_err(|_| format!("{name} must be set"))?);
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
+
+fn resolve_endpoint(endpoint: EndpointConfig) -> Result<Endpoint, Box<dyn Error>> {
+    let (url, headers, auth) = match endpoint {
+        EndpointConfig::Url(url) => (url, HashMap::new(), None),
+        EndpointConfig::Detailed { url, headers, auth } => (url, headers, auth),
+    };
+
+    Ok(Endpoint {
+        url: Url::parse(&url).map_err(|e| format!("invalid URL {url}: {e}"))?,
+        headers: headers
+            .into_iter()
+            .map(|(name, value)| Ok((name, expand_env(&value)?)))
+            .collect::<Result<_, Box<dyn Error>>>()?,
+        auth: match auth {
+            Some(AuthConfig::Bearer(token)) => Some(Auth::Bearer(expand_env(&token)?)),
+            Some(AuthConfig::Basic { username, password }) => Some(Auth::Basic {
+                username: expand_env(&username)?,
+                password: password.as_deref().map(expand_env).transpose()?,
+            }),
+            None => None,
+        },
+    })
+}
+
+fn parse_felt(name: &str, value: &str) -> Result<Felt, Box<dyn Error>> {
+    Felt::from_hex(value).map_err(|e| format!("{name} is not a valid felt: {e}").into())
+}
+
+/// Parses a hex felt, or a short string like `SN_SEPOLIA` otherwise
+pub fn parse_felt_or_short_string(name: &str, value: &str) -> Result<Felt, Box<dyn Error>> {
+    if value.starts_with("0x") {
+        parse_felt(name, value)
+    } else {
+        cairo_short_string_to_felt(value)
+            .map_err(|e| format!("{name} is not a valid short string: {e}").into())
+    }
+}
+
+fn parse_env<T: ValueEnum>(name: &str) -> Result<Option<T>, Box<dyn Error>> {
+    env::var(name)
+        .ok()
+        .map(|value| T::from_str(&value, true).map_err(|e| format!("{name}: {e}").into()))
+        .transpose()
+}
+
+fn load_config_file(path: &Path) -> Result<ConfigFile, Box<dyn Error>> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read config file {}: {e}", path.display()))?;
+    toml::from_str(&content)
+        .map_err(|e| format!("failed to parse config file {}: {e}", path.display()).into())
+}
+
+fn resolve_secret_key(file: &ConfigFile, signer: Option<&str>) -> Result<Felt, Box<dyn Error>> {
+    let (name, key) = signer_secret_key(file, signer, "DEPLOYER_SECRET_KEY")?;
+    parse_felt(&name, &key)
+}
+
+/// Secret key of the named signer, or of `default_var` without one. Returns the key along
+/// with its name for error messages.
+fn signer_secret_key(
+    file: &ConfigFile,
+    signer: Option<&str>,
+    default_var: &str,
+) -> Result<(String, String), Box<dyn Error>> {
+    // a named signer takes precedence over the default variable, otherwise selecting
+    // a signer from the CLI would be silently shadowed by the one in `.env`
+    let Some(name) = signer else {
+        let key = env::var(default_var).map_err(|_| format!("{default_var} must be set"))?;
+        return Ok((default_var.to_owned(), key));
+    };
+
+    let signer = file
+        .signers
+        .get(name)
+        .ok_or_else(|| format!("signer `{name}` not found in config file"))?;
+    let key = match (&signer.secret_key, &signer.secret_key_env) {
+        (Some(key), None) => key.clone(),
+        (None, Some(var)) => {
+            env::var(var).map_err(|_| format!("{var} must be set for signer `{name}`"))?
+        }
+        _ => {
+            return Err(format!(
+                "signer `{name}` must set exactly one of secret_key or secret_key_env"
+            )
+            .into())
+        }
+    };
+    Ok((format!("secret key of signer `{name}`"), key))
+}
+
+/// Merges the env and CLI layers on top of the named profile
+fn merge_profile(
+    file: &ConfigFile,
+    name: Option<&str>,
+    layers: [Profile; 2],
+) -> Result<Profile, Box<dyn Error>> {
+    let mut profile = match name {
+        Some(name) => file
+            .profiles
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("profile `{name}` not found in config file"))?,
+        None => Profile::default(),
+    };
+    for layer in layers {
+        profile.merge(layer);
+    }
+    Ok(profile)
+}
+
+/// Checks that the merged profile of a Starknet network is complete
+fn resolve_network(
+    file: &ConfigFile,
+    name: Option<String>,
+    profile: Profile,
+    (chain_id_var, rpc_url_var): (&str, &str),
+    default_fee_policy: FeePolicy,
+) -> Result<NetworkConfig, Box<dyn Error>> {
+    Ok(NetworkConfig {
+        chain_id: parse_felt_or_short_string(
+            chain_id_var,
+            &profile
+                .chain_id
+                .ok_or_else(|| format!("{chain_id_var} must be set"))?,
+        )?,
+        rpc_endpoints: profile
+            .rpc_url
+            .filter(|endpoints| !endpoints.is_empty())
+            .ok_or_else(|| format!("{rpc_url_var} must be set"))?
+            .into_iter()
+            .map(resolve_endpoint)
+            .collect::<Result<_, _>>()?,
+        retry: RetryPolicy {
+            max_retries: profile
+                .max_retries
+                .unwrap_or(RetryPolicy::default().max_retries),
+            base_delay: profile
+                .retry_base_delay_ms
+                .map_or(RetryPolicy::default().base_delay, Duration::from_millis),
+            ..RetryPolicy::default()
+        },
+        rpc_admin: profile.rpc_admin_url.map(resolve_endpoint).transpose()?,
+        ws: profile.ws_url.map(resolve_endpoint).transpose()?,
+        account_address: profile
+            .account_address
+            .map(|address| parse_felt("account address", &address))
+            .transpose()?,
+        governance_address: profile
+            .governance_address
+            .map(|address| parse_felt("governance address", &address))
+            .transpose()?,
+        genesis: profile.genesis.map(PathBuf::from),
+        secret_key: resolve_secret_key(file, profile.signer.as_deref())?,
+        tx: TxSettings {
+            version: profile.tx_version.unwrap_or(TxVersion::V1),
+            fee_policy: profile.fee_policy.unwrap_or(default_fee_policy),
+        },
+        block_id: profile.block_tag.unwrap_or(BlockTagConfig::Pending).into(),
+        udc: UdcSettings {
+            address: profile
+                .udc_address
+                .map(|address| parse_felt("UDC address", &address))
+                .transpose()?
+                .unwrap_or(UDC_ADDRESS),
+            uniqueness: profile.udc_uniqueness.unwrap_or(UdcUniqueness::NotUnique),
+        },
+        profile: name,
+    })
+}
+
+/// Checks that the merged profile of an Ethereum settlement chain is complete. Only the chain
+/// id, a single RPC endpoint and the signer apply, the account is the one of the signer.
+fn resolve_ethereum(file: &ConfigFile, profile: Profile) -> Result<EthereumConfig, Box<dyn Error>> {
+    let chain_id = profile.chain_id.ok_or("SETTLEMENT_CHAIN_ID must be set")?;
+    let chain_id = match chain_id.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => chain_id.parse(),
+    }
+    .map_err(|e| format!("SETTLEMENT_CHAIN_ID is not an Ethereum chain id: {e}"))?;
+
+    let endpoint = match profile.rpc_url.as_deref() {
+        Some([endpoint]) => resolve_endpoint(endpoint.clone())?,
+        Some([]) | None => return Err("SETTLEMENT_RPC_URL must be set".into()),
+        Some(_) => return Err("an Ethereum settlement chain takes a single RPC endpoint".into()),
+    };
+    if !endpoint.headers.is_empty() || endpoint.auth.is_some() {
+        return Err("headers and auth are not supported on an Ethereum settlement chain".into());
+    }
+
+    let (key_name, key) = signer_secret_key(file, profile.signer.as_deref(), "ETH_PRIVATE_KEY")?;
+    let secret_key = key
+        .parse::<B256>()
+        .map_err(|e| format!("{key_name} is not a valid private key: {e}"))?;
+
+    Ok(EthereumConfig {
+        chain_id,
+        rpc_url: endpoint.url,
+        secret_key,
+    })
+}
+
+/// Reads the config file given on the command line, in `QUAZA_CONFIG` or at the default path
+fn load_file(args: &ConfigArgs) -> Result<ConfigFile, Box<dyn Error>> {
     dotenv().ok();
 
-    Config {
-        rpc_url: env::var("RPC_URL").expect("RPC_URL must be set"),
-        rpc_admin_url: env::var("RPC_ADMIN_URL").expect("RPC_ADMIN_URL must be set"),
-        rpc_starknet_url: env::var("RPC_STARKNET_URL").expect("RPC_STARKNET_URL must be set"),
+    // an explicitly requested config file must exist, the default one is optional
+    let config_path = args
+        .config
+        .clone()
+        .or_else(|| env::var("QUAZA_CONFIG").ok().map(Into::into));
+    match &config_path {
+        Some(path) => load_config_file(path),
+        None if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+            load_config_file(Path::new(DEFAULT_CONFIG_PATH))
+        }
+        None => Ok(ConfigFile::default()),
+    }
+}
 
-        deployer_secret_key: Felt::from_hex_unchecked(
-            &env::var("DEPLOYER_SECRET_KEY").expect("DEPLOYER_SECRET_KEY must be set"),
-        ),
+fn l3_profile_name(args: &ConfigArgs, file: &ConfigFile) -> Option<String> {
+    args.profile
+        .clone()
+        .or_else(|| env::var("QUAZA_PROFILE").ok())
+        .or(file.default_profile.clone())
+}
+
+/// The settlement profile, the `settlement` field of the L3 profile unless overridden
+fn settlement_profile_name(
+    args: &ConfigArgs,
+    file: &ConfigFile,
+    l3_profile: Option<&str>,
+) -> Option<String> {
+    args.settlement_profile
+        .clone()
+        .or_else(|| env::var("QUAZA_SETTLEMENT_PROFILE").ok())
+        .or_else(|| {
+            l3_profile
+                .and_then(|name| file.profiles.get(name))
+                .and_then(|profile| profile.settlement.clone())
+        })
+}
+
+/// Resolves the L3 network alone, the admin endpoint is left optional
+fn resolve_l3(
+    args: &ConfigArgs,
+    file: &ConfigFile,
+    l3_profile: Option<String>,
+) -> Result<NetworkConfig, Box<dyn Error>> {
+    let l3_layers = merge_profile(
+        file,
+        l3_profile.as_deref(),
+        [
+            Profile {
+                chain_id: env::var("CHAIN_ID").ok(),
+                rpc_url: env::var("RPC_URL").ok().as_deref().map(split_urls),
+                rpc_admin_url: env::var("RPC_ADMIN_URL").ok().map(EndpointConfig::Url),
+                ws_url: env::var("WS_URL").ok().map(EndpointConfig::Url),
+                fee_policy: parse_env("FEE_POLICY")?,
+                tx_version: parse_env("TX_VERSION")?,
+                block_tag: parse_env("BLOCK_TAG")?,
+                signer: env::var("SIGNER").ok(),
+                account_address: env::var("ACCOUNT_ADDRESS").ok(),
+                governance_address: env::var("GOVERNANCE_ADDRESS").ok(),
+                genesis: env::var("GENESIS").ok(),
+                udc_address: env::var("UDC_ADDRESS").ok(),
+                udc_uniqueness: parse_env("UDC_UNIQUENESS")?,
+                ..Default::default()
+            },
+            Profile {
+                chain_id: args.chain_id.clone(),
+                rpc_url: args.rpc_url.as_deref().map(split_urls),
+                rpc_admin_url: args.rpc_admin_url.clone().map(EndpointConfig::Url),
+                ws_url: args.ws_url.clone().map(EndpointConfig::Url),
+                fee_policy: args.fee_policy,
+                tx_version: args.tx_version,
+                block_tag: args.block_tag,
+                signer: args.signer.clone(),
+                account_address: args.account_address.clone(),
+                governance_address: args.governance_address.clone(),
+                genesis: args.genesis.clone(),
+                udc_address: args.udc_address.clone(),
+                udc_uniqueness: args.udc_uniqueness,
+                ..Default::default()
+            },
+        ],
+    )?;
+    resolve_network(
+        file,
+        l3_profile,
+        l3_layers,
+        ("CHAIN_ID", "RPC_URL"),
+        FeePolicy::Free,
+    )
+}
 
-        starknet_account_address: Felt::from_hex_unchecked(
-            &env::var("STARKNET_ACCOUNT_ADDRESS").expect("STARKNET_ACCOUNT_ADDRESS must be set"),
+/// Loads the settings of the commands that only talk to the L3 (`faucet`, `upgrade`,
+/// `storage`), without requiring the admin endpoint or a settlement chain
+pub fn load_l3_config(args: &ConfigArgs) -> Result<L3Config, Box<dyn Error>> {
+    let file = load_file(args)?;
+    let l3_profile = l3_profile_name(args, &file);
+
+    Ok(L3Config {
+        l3: resolve_l3(args, &file, l3_profile)?,
+        allow_mainnet: args.allow_mainnet,
+    })
+}
+
+/// Builds the configuration from, in increasing order of precedence:
+/// the selected profiles of the config file, the environment (and `.env`), and the CLI flags.
+pub fn load_config(args: &ConfigArgs) -> Result<Config, Box<dyn Error>> {
+    let file = load_file(args)?;
+
+    let l3_profile = l3_profile_name(args, &file);
+    let settlement_profile = settlement_profile_name(args, &file, l3_profile.as_deref());
+
+    let l3 = resolve_l3(args, &file, l3_profile)?;
+    if l3.rpc_admin.is_none() {
+        return Err("RPC_ADMIN_URL must be set".into());
+    }
+
+    let settlement_layers = merge_profile(
+        &file,
+        settlement_profile.as_deref(),
+        [
+            Profile {
+                chain_id: env::var("SETTLEMENT_CHAIN_ID").ok(),
+                // RPC_STARKNET_URL predates Ethereum settlement and is still read
+                rpc_url: env::var("SETTLEMENT_RPC_URL")
+                    .or_else(|_| env::var("RPC_STARKNET_URL"))
+                    .ok()
+                    .as_deref()
+                    .map(split_urls),
+                ws_url: env::var("SETTLEMENT_WS_URL").ok().map(EndpointConfig::Url),
+                account_address: env::var("STARKNET_ACCOUNT_ADDRESS").ok(),
+                fee_policy: parse_env("SETTLEMENT_FEE_POLICY")?,
+                tx_version: parse_env("SETTLEMENT_TX_VERSION")?,
+                block_tag: parse_env("SETTLEMENT_BLOCK_TAG")?,
+                signer: env::var("SETTLEMENT_SIGNER").ok(),
+                udc_address: env::var("SETTLEMENT_UDC_ADDRESS").ok(),
+                udc_uniqueness: parse_env("SETTLEMENT_UDC_UNIQUENESS")?,
+                layer: parse_env("SETTLEMENT_LAYER")?,
+                ..Default::default()
+            },
+            Profile {
+                chain_id: args.settlement_chain_id.clone(),
+                rpc_url: args.settlement_rpc_url.as_deref().map(split_urls),
+                ws_url: args.settlement_ws_url.clone().map(EndpointConfig::Url),
+                account_address: args.starknet_account_address.clone(),
+                fee_policy: args.settlement_fee_policy,
+                tx_version: args.settlement_tx_version,
+                block_tag: args.settlement_block_tag,
+                signer: args.settlement_signer.clone(),
+                udc_address: args.settlement_udc_address.clone(),
+                udc_uniqueness: args.settlement_udc_uniqueness,
+                layer: args.settlement_layer,
+                ..Default::default()
+            },
+        ],
+    )?;
+    let layer = settlement_layers.layer.unwrap_or(SettlementLayer::Starknet);
+    let settlement = match layer {
+        SettlementLayer::Starknet => {
+            let settlement = resolve_network(
+                &file,
+                settlement_profile,
+                settlement_layers,
+                ("SETTLEMENT_CHAIN_ID", "SETTLEMENT_RPC_URL"),
+                FeePolicy::Estimate,
+            )?;
+            if settlement.account_address.is_none() {
+                return Err("STARKNET_ACCOUNT_ADDRESS must be set".into());
+            }
+            Settlement::Starknet(Box::new(settlement))
+        }
+        SettlementLayer::Ethereum => {
+            Settlement::Ethereum(resolve_ethereum(&file, settlement_layers)?)
+        }
+    };
+
+    let ready_timeout = match (args.ready_timeout, env::var("READY_TIMEOUT").ok()) {
+        (Some(secs), _) => Duration::from_secs(secs),
+        (None, Some(secs)) => Duration::from_secs(
+            secs.parse()
+                .map_err(|e| format!("READY_TIMEOUT is not a number of seconds: {e}"))?,
         ),
+        (None, None) => file
+            .ready_timeout
+            .map_or(DEFAULT_READY_TIMEOUT, Duration::from_secs),
+    };
+
+    let strk_migration = args
+        .strk_migration
+        .clone()
+        .or_else(|| env::var_os("STRK_MIGRATION").map(PathBuf::from))
+        .or(file.strk_migration)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_STRK_MIGRATION_PATH));
+    let eth_salt = args
+        .eth_salt
+        .clone()
+        .or_else(|| env::var("ETH_SALT").ok())
+        .or(file.eth_salt)
+        .map(|salt| parse_felt("ETH_SALT", &salt))
+        .transpose()?;
+    let eth_migration = args
+        .eth_migration
+        .clone()
+        .or_else(|| env::var_os("ETH_MIGRATION").map(PathBuf::from))
+        .or(file.eth_migration)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_ETH_MIGRATION_PATH));
+
+    let salts = Salts {
+        namespace: args
+            .salt_namespace
+            .clone()
+            .or_else(|| env::var("SALT_NAMESPACE").ok())
+            .or(file.salt_namespace),
+        overrides: file
+            .salts
+            .into_iter()
+            .map(|(name, salt)| {
+                if !Salts::NAMES.contains(&name.as_str()) {
+                    return Err(format!(
+                        "unknown salt `{name}`, expected one of {}",
+                        Salts::NAMES.join(", ")
+                    )
+                    .into());
+                }
+                Ok((name.clone(), parse_felt(&format!("salt of {name}"), &salt)?))
+            })
+            .collect::<Result<_, Box<dyn Error>>>()?,
+    };
+
+    let core_contract = resolve_core_contract(args, file.core_contract, layer)?;
+
+    Ok(Config {
+        l3,
+        settlement,
+        allow_mainnet: args.allow_mainnet,
+        ready_timeout,
+        strk_migration,
+        eth_salt,
+        eth_migration,
+        salts,
+        core_contract,
+    })
+}
+
+/// Merges the env and CLI overrides on top of the `[core_contract]` section
+fn resolve_core_contract(
+    args: &ConfigArgs,
+    file: CoreContractFile,
+    layer: SettlementLayer,
+) -> Result<CoreContractConfig, Box<dyn Error>> {
+    let class_hash = args
+        .core_contract_class_hash
+        .clone()
+        .or_else(|| env::var("CORE_CONTRACT_CLASS_HASH").ok())
+        .or(file.class_hash);
+    let class = match (class_hash, file.class, file.compiled_class) {
+        (Some(_), Some(_), _) => {
+            return Err("core contract `class_hash` and `class` can't be both set".into())
+        }
+        (Some(class_hash), None, _) => {
+            CoreContractClass::Declared(parse_felt("CORE_CONTRACT_CLASS_HASH", &class_hash)?)
+        }
+        (None, Some(class), Some(compiled_class)) => CoreContractClass::Artifact {
+            class,
+            compiled_class,
+        },
+        (None, Some(_), None) => {
+            return Err("core contract `compiled_class` must be set along with `class`".into())
+        }
+        (None, None, _) => CoreContractClass::Declared(DEFAULT_CORE_CONTRACT_CLASS_HASH),
+    };
+
+    let owner = args
+        .core_contract_owner
+        .clone()
+        .or_else(|| env::var("CORE_CONTRACT_OWNER").ok())
+        .or(file.owner)
+        .map(|owner| parse_felt("CORE_CONTRACT_OWNER", &expand_env(&owner)?))
+        .transpose()?;
+
+    let block_number = match (
+        args.core_contract_block_number,
+        env::var("CORE_CONTRACT_BLOCK_NUMBER").ok(),
+    ) {
+        (Some(block_number), _) => block_number,
+        (None, Some(block_number)) => block_number
+            .parse()
+            .map_err(|e| format!("CORE_CONTRACT_BLOCK_NUMBER is not a block number: {e}"))?,
+        (None, None) => file.block_number.unwrap_or(0),
+    };
+
+    let felt = |name: &str, value: &str| parse_felt_or_short_string(name, &expand_env(value)?);
+    let program_info = match (&file.program_hash, &file.config_hash) {
+        (Some(program_hash), Some(config_hash)) => Some((
+            felt("core contract program_hash", program_hash)?,
+            felt("core contract config_hash", config_hash)?,
+        )),
+        (None, None) => None,
+        _ => return Err("core contract `program_hash` and `config_hash` go together".into()),
+    };
+    let operators = file
+        .operators
+        .iter()
+        .map(|operator| felt("core contract operator", operator))
+        .collect::<Result<_, _>>()?;
+    let calls = file
+        .calls
+        .iter()
+        .map(|call| {
+            let calldata = call
+                .calldata
+                .iter()
+                .map(|value| felt(&format!("calldata of {}", call.function), value))
+                .collect::<Result<_, _>>()?;
+            Ok((get_selector_from_name(&call.function)?, calldata))
+        })
+        .collect::<Result<_, Box<dyn Error>>>()?;
+
+    let ethereum = EthereumCoreContractConfig {
+        core_contract_artifact: file
+            .ethereum
+            .core_contract_artifact
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_ETHEREUM_CORE_CONTRACT_PATH)),
+        proxy_artifact: file
+            .ethereum
+            .proxy_artifact
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_ETHEREUM_PROXY_PATH)),
+        verifier: file
+            .ethereum
+            .verifier
+            .map(|verifier| parse_felt("core contract verifier", &expand_env(&verifier)?))
+            .transpose()?,
+        aggregator_program_hash: file
+            .ethereum
+            .aggregator_program_hash
+            .map(|hash| felt("core contract aggregator_program_hash", &hash))
+            .transpose()?,
+        upgrade_delay: file.ethereum.upgrade_delay.unwrap_or(0),
+    };
+    if layer == SettlementLayer::Ethereum {
+        // the Solidity core contract rejects an initialization without them
+        if program_info.is_none() || ethereum.verifier.is_none() {
+            return Err(
+                "core contract `program_hash`, `config_hash` and `ethereum.verifier` \
+                        must be set to settle on Ethereum"
+                    .into(),
+            );
+        }
+        if !file.calls.is_empty() {
+            return Err(
+                "core contract `calls` are only sent on a Starknet settlement chain".into(),
+            );
+        }
+    }
+
+    Ok(CoreContractConfig {
+        class,
+        owner,
+        block_number,
+        program_info,
+        operators,
+        calls,
+        ethereum,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Cli;
+    use clap::Parser;
+
+    const FILE: &str = r#"
+        default_profile = "l3"
+
+        [signers.inline]
+        secret_key = "0x1"
+
+        [profiles.l3]
+        chain_id = "FILE_CHAIN"
+        rpc_url = "http://file:9944/"
+        fee_policy = "estimate"
+        signer = "inline"
+        settlement = "l2"
+
+        [profiles.l2]
+        chain_id = "SN_SEPOLIA"
+        rpc_url = ["http://first:9944/", "http://second:9944/"]
+        signer = "inline"
+
+        [profiles.eth]
+        layer = "ethereum"
+        chain_id = "31337"
+        rpc_url = "http://localhost:8545"
+    "#;
+
+    fn args(flags: &[&str]) -> ConfigArgs {
+        Cli::parse_from([&["deploy_quaza"], flags].concat()).config
+    }
+
+    #[test]
+    fn example_config_parses() {
+        let file: ConfigFile = toml::from_str(include_str!("../exemple.toml")).unwrap();
+        assert_eq!(file.default_profile.as_deref(), Some("local"));
+        assert_eq!(
+            file.profiles["local"].settlement.as_deref(),
+            Some("sepolia")
+        );
+        assert_eq!(file.profiles["sepolia"].rpc_url.as_ref().unwrap().len(), 2);
+        assert_eq!(
+            file.profiles["anvil"].layer,
+            Some(SettlementLayer::Ethereum)
+        );
+        assert!(file.signers.contains_key("devnet"));
+    }
+
+    #[test]
+    fn env_and_cli_layers_override_the_profile() {
+        let file: ConfigFile = toml::from_str(FILE).unwrap();
+        let profile = merge_profile(
+            &file,
+            Some("l3"),
+            [
+                Profile {
+                    chain_id: Some("ENV_CHAIN".to_owned()),
+                    rpc_url: Some(split_urls("http://env:9944/, http://env2:9944/")),
+                    ..Default::default()
+                },
+                Profile {
+                    chain_id: Some("CLI_CHAIN".to_owned()),
+                    tx_version: Some(TxVersion::V3),
+                    ..Default::default()
+                },
+            ],
+        )
+        .unwrap();
+        let network = resolve_network(
+            &file,
+            Some("l3".to_owned()),
+            profile,
+            ("CHAIN_ID", "RPC_URL"),
+            FeePolicy::Free,
+        )
+        .unwrap();
+
+        assert_eq!(
+            network.chain_id,
+            cairo_short_string_to_felt("CLI_CHAIN").unwrap()
+        );
+        let urls: Vec<_> = network
+            .rpc_endpoints
+            .iter()
+            .map(|endpoint| endpoint.url.as_str())
+            .collect();
+        assert_eq!(urls, ["http://env:9944/", "http://env2:9944/"]);
+        assert_eq!(network.tx.version, TxVersion::V3);
+        assert_eq!(network.tx.fee_policy, FeePolicy::Estimate);
+        assert_eq!(network.block_id, BlockId::Tag(BlockTag::Pending));
+        assert_eq!(network.secret_key, Felt::ONE);
+        assert_eq!(network.udc.address, UDC_ADDRESS);
+    }
+
+    #[test]
+    fn incomplete_or_unknown_profiles_are_rejected() {
+        let file: ConfigFile = toml::from_str(FILE).unwrap();
+        assert!(merge_profile(&file, Some("missing"), Default::default())
+            .unwrap_err()
+            .to_string()
+            .contains("profile `missing` not found"));
+
+        let profile = merge_profile(
+            &file,
+            Some("l3"),
+            [
+                Profile::default(),
+                Profile {
+                    rpc_url: Some(Vec::new()),
+                    ..Default::default()
+                },
+            ],
+        )
+        .unwrap();
+        let error = resolve_network(
+            &file,
+            None,
+            profile,
+            ("CHAIN_ID", "RPC_URL"),
+            FeePolicy::Free,
+        )
+        .unwrap_err();
+        assert_eq!(error.to_string(), "RPC_URL must be set");
+    }
+
+    #[test]
+    fn settlement_profile_follows_the_l3_profile() {
+        let file: ConfigFile = toml::from_str(FILE).unwrap();
+
+        let l3 = l3_profile_name(&args(&[]), &file);
+        assert_eq!(l3.as_deref(), Some("l3"));
+        assert_eq!(
+            settlement_profile_name(&args(&[]), &file, l3.as_deref()).as_deref(),
+            Some("l2")
+        );
+
+        let overridden = args(&["--profile", "l2", "--settlement-profile", "eth"]);
+        let l3 = l3_profile_name(&overridden, &file);
+        assert_eq!(l3.as_deref(), Some("l2"));
+        assert_eq!(
+            settlement_profile_name(&overridden, &file, l3.as_deref()).as_deref(),
+            Some("eth")
+        );
+        assert_eq!(
+            settlement_profile_name(&args(&["--profile", "l2"]), &file, Some("l2")),
+            None
+        );
     }
 }
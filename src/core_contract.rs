@@ -0,0 +1,186 @@
+use starknet::{
+    accounts::{Account, ConnectedAccount, SingleOwnerAccount},
+    core::types::{Call, Felt},
+    macros::selector,
+    providers::jsonrpc::JsonRpcClient,
+    signers::LocalWallet,
+};
+use std::{error::Error, path::Path};
+
+use crate::{
+    account::{declare_v2_if_missing, execute, is_declared, sierra_class_hash},
+    config::{CoreContractClass, CoreContractConfig, NetworkConfig, Salts},
+    deploy::{deploy_udc, is_deployed},
+    ownership::transfer_ownership,
+    provider::FailoverTransport,
+    utils::{confirm_declaration, wait_for_confirmation},
+    ws::WsClient,
+};
+
+/// Artifact of the core contract declared when its class is missing from the settlement chain
+const CORE_CONTRACT_CLASS_PATH: &str = "./contracts/core_contract/contract_class.json";
+const CORE_CONTRACT_COMPILED_CLASS_PATH: &str =
+    "./contracts/core_contract/compiled_contract_class.json";
+
+/// Checks that the artifact shipped in `contracts/core_contract` has the expected class hash
+fn check_shipped_class(class_hash: Felt) -> Result<(), Box<dyn Error>> {
+    if !Path::new(CORE_CONTRACT_CLASS_PATH).exists() {
+        return Err(format!(
+            "core contract class 0x{:x} is not declared on the settlement chain and \
+             {CORE_CONTRACT_CLASS_PATH} is missing, set `class` and `compiled_class` in \
+             [core_contract] to declare it",
+            class_hash
+        )
+        .into());
+    }
+    let shipped_class_hash = sierra_class_hash(CORE_CONTRACT_CLASS_PATH)?;
+    if shipped_class_hash != class_hash {
+        return Err(format!(
+            "core contract class 0x{:x} is not declared on the settlement chain and \
+             {CORE_CONTRACT_CLASS_PATH} has class hash 0x{:x}",
+            class_hash, shipped_class_hash
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Checks that the class of the core contract is declared on the settlement chain or can be
+/// declared from its artifact, so that a missing class fails before the L3 is bootstrapped
+pub async fn check_core_contract_class(
+    provider: &JsonRpcClient<FailoverTransport>,
+    settlement: &NetworkConfig,
+    core_contract: &CoreContractConfig,
+) -> Result<(), Box<dyn Error>> {
+    match &core_contract.class {
+        CoreContractClass::Artifact { class, .. } => {
+            sierra_class_hash(class)
+                .map_err(|e| format!("failed to read the core contract class {class}: {e}"))?;
+        }
+        CoreContractClass::Declared(class_hash) => {
+            if !is_declared(provider, settlement.block_id, *class_hash).await? {
+                check_shipped_class(*class_hash)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Class hash of the core contract, declared first when missing
+async fn core_contract_class_hash(
+    account: &SingleOwnerAccount<&JsonRpcClient<FailoverTransport>, &LocalWallet>,
+    ws: Option<&WsClient>,
+    settlement: &NetworkConfig,
+    core_contract: &CoreContractConfig,
+) -> Result<Felt, Box<dyn Error>> {
+    let (class, compiled_class) = match &core_contract.class {
+        CoreContractClass::Artifact {
+            class,
+            compiled_class,
+        } => (class.as_str(), compiled_class.as_str()),
+        CoreContractClass::Declared(class_hash) => {
+            if is_declared(account.provider(), account.block_id(), *class_hash).await? {
+                return Ok(*class_hash);
+            }
+            check_shipped_class(*class_hash)?;
+            (CORE_CONTRACT_CLASS_PATH, CORE_CONTRACT_COMPILED_CLASS_PATH)
+        }
+    };
+
+    confirm_declaration(
+        account.provider(),
+        ws,
+        "Core contract",
+        declare_v2_if_missing(account, class, compiled_class, settlement.tx).await?,
+    )
+    .await
+}
+
+/// Deploys the core contract from the state of the L3 block, sends the initialization calls
+/// and hands it over to the configured owner. An existing deployment is reused as is.
+pub async fn deploy_core_contract(
+    account: &SingleOwnerAccount<&JsonRpcClient<FailoverTransport>, &LocalWallet>,
+    ws: Option<&WsClient>,
+    settlement: &NetworkConfig,
+    core_contract: &CoreContractConfig,
+    salts: &Salts,
+    state_root: Felt,
+    block_hash: Felt,
+) -> Result<Felt, Box<dyn Error>> {
+    let class_hash = core_contract_class_hash(account, ws, settlement, core_contract).await?;
+    let salt = salts.get("core_contract", Felt::ZERO);
+    // initialized by the deployer, then handed over
+    let calldata = vec![
+        account.address(),
+        state_root,
+        Felt::from(core_contract.block_number),
+        block_hash,
+    ];
+
+    let predicted = settlement
+        .udc
+        .deployed_address(account.address(), salt, class_hash, &calldata);
+    if is_deployed(
+        account.provider(),
+        account.block_id(),
+        predicted,
+        class_hash,
+    )
+    .await?
+    {
+        return Ok(predicted);
+    }
+    let address = deploy_udc(
+        account,
+        ws,
+        class_hash,
+        salt,
+        &calldata,
+        settlement.udc,
+        settlement.tx,
+    )
+    .await?;
+
+    let mut calls = vec![];
+    if let Some((program_hash, config_hash)) = core_contract.program_info {
+        calls.push(Call {
+            to: address,
+            selector: selector!("set_program_info"),
+            calldata: vec![program_hash, config_hash],
+        });
+    }
+    for operator in &core_contract.operators {
+        calls.push(Call {
+            to: address,
+            selector: selector!("register_operator"),
+            calldata: vec![*operator],
+        });
+    }
+    for (selector, calldata) in &core_contract.calls {
+        calls.push(Call {
+            to: address,
+            selector: *selector,
+            calldata: calldata.clone(),
+        });
+    }
+    if !calls.is_empty() {
+        let count = calls.len();
+        let tx_hash = execute(account, calls, settlement.tx)
+            .await?
+            .transaction_hash;
+        wait_for_confirmation(account.provider(), ws, tx_hash).await?;
+        println!(
+            "Core contract initialized with {} calls: 0x{:x}",
+            count, tx_hash
+        );
+    }
+
+    if let Some(owner) = core_contract.owner {
+        transfer_ownership(account, ws, address, owner, settlement.tx).await?;
+        println!("Core contract ownership transferred to 0x{:x}", owner);
+    }
+
+    Ok(address)
+}
@@ -2,9 +2,17 @@ use std::error::Error;
 use std::fs::File;
 
 use serde_json::json;
-use starknet::core::types::{
-    contract::legacy::LegacyContractClass, CompressedLegacyContractClass, DeclareTransactionResult,
-    Felt,
+use starknet::{
+    core::types::{
+        contract::legacy::LegacyContractClass, BlockId, CompressedLegacyContractClass,
+        DeclareTransactionResult, Felt,
+    },
+    providers::JsonRpcClient,
+};
+
+use crate::{
+    account::{is_declared, legacy_class_hash},
+    provider::{http_client, Endpoint, FailoverTransport},
 };
 
 #[derive(Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
@@ -29,7 +37,7 @@ struct JsonRpcResponse<T> {
 }
 
 pub async fn declare_v0(
-    url: &str,
+    admin: &Endpoint,
     path: &str,
     sender_address: Felt,
 ) -> Result<DeclareTransactionResult, Box<dyn Error>> {
@@ -51,9 +59,8 @@ pub async fn declare_v0(
         "id": 0
     });
 
-    let client = reqwest::Client::new();
-    client
-        .post(url)
+    admin
+        .post(&http_client())
         .json(body)
         .send()
         .await?
@@ -62,3 +69,20 @@ pub async fn declare_v0(
         .map(|res| res.result)
         .map_err(Into::into)
 }
+
+/// Declares the legacy class through the admin endpoint unless it is already declared, returns
+/// its hash and the declare transaction hash if one was sent
+pub async fn declare_v0_if_missing(
+    admin: &Endpoint,
+    provider: &JsonRpcClient<FailoverTransport>,
+    block_id: BlockId,
+    path: &str,
+    sender_address: Felt,
+) -> Result<(Felt, Option<Felt>), Box<dyn Error>> {
+    let class_hash = legacy_class_hash(path)?;
+    if is_declared(provider, block_id, class_hash).await? {
+        return Ok((class_hash, None));
+    }
+    let result = declare_v0(admin, path, sender_address).await?;
+    Ok((result.class_hash, Some(result.transaction_hash)))
+}
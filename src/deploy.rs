@@ -1,32 +1,155 @@
 use starknet::{
-    accounts::{Account, SingleOwnerAccount},
+    accounts::{Account, ConnectedAccount, SingleOwnerAccount},
     contract::ContractFactory,
     core::{
-        types::{Call, Felt},
-        utils::get_udc_deployed_address,
+        types::{
+            BlockId, Call, ExecuteInvocation, Felt, FunctionInvocation, InvokeTransactionTrace,
+            StarknetError, TransactionReceipt, TransactionTrace,
+        },
+        utils::get_contract_address,
     },
     macros::selector,
-    providers::jsonrpc::{HttpTransport, JsonRpcClient},
+    providers::{jsonrpc::JsonRpcClient, Provider, ProviderError},
     signers::LocalWallet,
 };
 use std::error::Error;
 
+use crate::{
+    account::execute,
+    config::{TxSettings, TxVersion, UdcSettings, UDC_ADDRESS, UDC_CLASS_HASH},
+    provider::FailoverTransport,
+    utils::wait_for_confirmation,
+    ws::WsClient,
+};
+
+/// Where the address actually deployed by a transaction is read from
+pub enum DeployedAddress {
+    /// Value returned by the `deploy_contract` call of the account in the transaction trace,
+    /// the deploy syscall doesn't emit any event
+    DeployContract { account: Felt },
+    /// `ContractDeployed` event emitted by the UDC in the receipt
+    UdcEvent { udc: Felt },
+    /// Address of a `DEPLOY_ACCOUNT` receipt
+    DeployAccount,
+}
+
+/// Whether `class_hash` is already deployed at `address`, failing if another class is there so
+/// that a salt collision doesn't end up as a reverted deployment
+pub async fn is_deployed<P: Provider + Sync>(
+    provider: &P,
+    block_id: BlockId,
+    address: Felt,
+    class_hash: Felt,
+) -> Result<bool, Box<dyn Error>> {
+    match provider.get_class_hash_at(block_id, address).await {
+        Ok(deployed_class_hash) if deployed_class_hash == class_hash => {
+            println!("0x{:x} is already deployed with the same class", address);
+            Ok(true)
+        }
+        Ok(deployed_class_hash) => Err(format!(
+            "0x{:x} is already deployed with class 0x{:x} instead of 0x{:x}, use another salt",
+            address, deployed_class_hash, class_hash
+        )
+        .into()),
+        Err(ProviderError::StarknetError(StarknetError::ContractNotFound)) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn find_invocation(
+    invocation: &FunctionInvocation,
+    contract: Felt,
+    selector: Felt,
+) -> Option<&FunctionInvocation> {
+    if invocation.contract_address == contract && invocation.entry_point_selector == selector {
+        return Some(invocation);
+    }
+    invocation
+        .calls
+        .iter()
+        .find_map(|call| find_invocation(call, contract, selector))
+}
+
+/// Waits for a deployment, then checks that the chain deployed the contract at the predicted
+/// address
+pub async fn confirm_deployment(
+    provider: &JsonRpcClient<FailoverTransport>,
+    ws: Option<&WsClient>,
+    tx_hash: Felt,
+    predicted: Felt,
+    source: DeployedAddress,
+) -> Result<(), Box<dyn Error>> {
+    wait_for_confirmation(provider, ws, tx_hash).await?;
+
+    let deployed = match source {
+        DeployedAddress::DeployContract { account } => {
+            let invocation = match provider.trace_transaction(tx_hash).await? {
+                TransactionTrace::Invoke(InvokeTransactionTrace {
+                    execute_invocation: ExecuteInvocation::Success(invocation),
+                    ..
+                }) => invocation,
+                _ => return Err(format!("0x{:x} is not a successful invoke", tx_hash).into()),
+            };
+            find_invocation(&invocation, account, selector!("deploy_contract"))
+                .and_then(|call| call.result.first().copied())
+        }
+        DeployedAddress::UdcEvent { udc } => provider
+            .get_transaction_receipt(tx_hash)
+            .await?
+            .receipt
+            .events()
+            .iter()
+            .find(|event| {
+                event.from_address == udc
+                    && event.keys.first() == Some(&selector!("ContractDeployed"))
+            })
+            .and_then(|event| event.data.first().copied()),
+        DeployedAddress::DeployAccount => {
+            match provider.get_transaction_receipt(tx_hash).await?.receipt {
+                TransactionReceipt::DeployAccount(receipt) => Some(receipt.contract_address),
+                _ => None,
+            }
+        }
+    }
+    .ok_or_else(|| format!("no deployed address found in transaction 0x{:x}", tx_hash))?;
+
+    if deployed != predicted {
+        return Err(format!(
+            "transaction 0x{:x} deployed 0x{:x}, predicted 0x{:x}",
+            tx_hash, deployed, predicted
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Deploys a contract from zero through the legacy deployer account and returns its address,
+/// nothing is sent if the contract is already deployed
 pub async fn deploy(
-    account: &SingleOwnerAccount<&JsonRpcClient<HttpTransport>, &LocalWallet>,
+    account: &SingleOwnerAccount<&JsonRpcClient<FailoverTransport>, &LocalWallet>,
+    ws: Option<&WsClient>,
     class_hash: Felt,
     salt: Felt,
     constructor_calldata: &[Felt],
-) -> Result<(Felt, Felt), Box<dyn Error>> {
+    tx: TxSettings,
+) -> Result<Felt, Box<dyn Error>> {
     let mut calldata = vec![class_hash, salt, constructor_calldata.len().into()];
     calldata.extend_from_slice(constructor_calldata);
     calldata.push(Felt::ONE); // deploy from zero
 
-    let contract_address = get_udc_deployed_address(
-        salt,
+    // deployed from zero, the address doesn't depend on the deployer account
+    let contract_address = get_contract_address(salt, class_hash, constructor_calldata, Felt::ZERO);
+    if is_deployed(
+        account.provider(),
+        account.block_id(),
+        contract_address,
         class_hash,
-        &starknet::core::utils::UdcUniqueness::NotUnique,
-        constructor_calldata,
-    );
+    )
+    .await?
+    {
+        return Ok(contract_address);
+    }
 
     let call = Call {
         to: account.address(),
@@ -34,38 +157,119 @@ pub async fn deploy(
         calldata,
     };
 
-    let result = account
-        .execute_v1(vec![call])
-        .max_fee(Felt::ZERO)
-        .send()
+    // the legacy deployer account only supports v1 transactions
+    let legacy_tx = TxSettings {
+        version: TxVersion::V1,
+        ..tx
+    };
+    let result = execute(account, vec![call], legacy_tx).await?;
+    confirm_deployment(
+        account.provider(),
+        ws,
+        result.transaction_hash,
+        contract_address,
+        DeployedAddress::DeployContract {
+            account: account.address(),
+        },
+    )
+    .await?;
+
+    Ok(contract_address)
+}
+
+/// Deploys the UDC with the parameters of its Starknet deployment, so that it lands on the
+/// address `ContractFactory` uses by default, and checks the deployed class
+pub async fn deploy_canonical_udc(
+    account: &SingleOwnerAccount<&JsonRpcClient<FailoverTransport>, &LocalWallet>,
+    ws: Option<&WsClient>,
+    class_hash: Felt,
+    tx: TxSettings,
+) -> Result<Felt, Box<dyn Error>> {
+    if class_hash != UDC_CLASS_HASH {
+        return Err(format!(
+            "UDC class hash is 0x{:x}, expected the Starknet UDC class 0x{:x}",
+            class_hash, UDC_CLASS_HASH
+        )
+        .into());
+    }
+
+    let udc_address = deploy(account, ws, class_hash, Felt::ZERO, &[], tx).await?;
+    if udc_address != UDC_ADDRESS {
+        return Err(format!(
+            "UDC deployed at 0x{:x} instead of 0x{:x}",
+            udc_address, UDC_ADDRESS
+        )
+        .into());
+    }
+
+    let deployed_class_hash = account
+        .provider()
+        .get_class_hash_at(account.block_id(), udc_address)
         .await?;
+    if deployed_class_hash != class_hash {
+        return Err(format!(
+            "class of the UDC at 0x{:x} is 0x{:x}, expected 0x{:x}",
+            udc_address, deployed_class_hash, class_hash
+        )
+        .into());
+    }
 
-    Ok((result.transaction_hash, contract_address))
+    Ok(udc_address)
 }
 
-pub async fn deploy_v1(
-    account: &SingleOwnerAccount<&JsonRpcClient<HttpTransport>, &LocalWallet>,
+/// Deploys a contract through the UDC and returns its address, nothing is sent if the contract
+/// is already deployed
+pub async fn deploy_udc(
+    account: &SingleOwnerAccount<&JsonRpcClient<FailoverTransport>, &LocalWallet>,
+    ws: Option<&WsClient>,
     class_hash: Felt,
     salt: Felt,
     constructor_calldata: &[Felt],
-    no_fee: bool,
-) -> Result<(Felt, Felt), Box<dyn Error>> {
-    let contract_address = get_udc_deployed_address(
-        salt,
+    udc: UdcSettings,
+    tx: TxSettings,
+) -> Result<Felt, Box<dyn Error>> {
+    let contract_address =
+        udc.deployed_address(account.address(), salt, class_hash, constructor_calldata);
+    if is_deployed(
+        account.provider(),
+        account.block_id(),
+        contract_address,
         class_hash,
-        &starknet::core::utils::UdcUniqueness::NotUnique,
-        &constructor_calldata,
-    );
-
-    let contract_factory = ContractFactory::new(class_hash, account);
-
-    let mut deploy = contract_factory.deploy_v1(constructor_calldata.to_vec(), salt, false);
-
-    if no_fee {
-        deploy = deploy.max_fee(Felt::ZERO);
+    )
+    .await?
+    {
+        return Ok(contract_address);
     }
 
-    let result = deploy.send().await?;
+    let contract_factory = ContractFactory::new_with_udc(class_hash, account, udc.address);
+
+    #[allow(deprecated)]
+    let result = match tx.version {
+        TxVersion::V1 => {
+            let mut deploy =
+                contract_factory.deploy_v1(constructor_calldata.to_vec(), salt, udc.unique());
+            if tx.no_fee() {
+                deploy = deploy.max_fee(Felt::ZERO);
+            }
+            deploy.send().await?
+        }
+        TxVersion::V3 => {
+            let mut deploy =
+                contract_factory.deploy_v3(constructor_calldata.to_vec(), salt, udc.unique());
+            if tx.no_fee() {
+                deploy = deploy.gas(0).gas_price(0);
+            }
+            deploy.send().await?
+        }
+    };
+    confirm_deployment(
+        account.provider(),
+        ws,
+        result.transaction_hash,
+        contract_address,
+        DeployedAddress::UdcEvent { udc: udc.address },
+    )
+    .await?;
 
-    Ok((result.transaction_hash, contract_address))
+    Ok(contract_address)
 }
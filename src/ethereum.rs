@@ -0,0 +1,273 @@
+use alloy::{
+    network::{Ethereum, EthereumWallet, TransactionBuilder},
+    primitives::{hex, Address, Bytes, I256, U256},
+    providers::{DynProvider, PendingTransactionBuilder, Provider, ProviderBuilder},
+    rpc::types::{TransactionReceipt, TransactionRequest},
+    signers::local::PrivateKeySigner,
+    sol,
+    sol_types::SolValue,
+};
+use serde_json::Value;
+use starknet::core::types::Felt;
+use std::{error::Error, fs, path::Path};
+
+use crate::config::{CoreContractConfig, EthereumConfig};
+
+/// Chain id of Ethereum mainnet
+const MAINNET_CHAIN_ID: u64 = 1;
+
+sol! {
+    /// StarkWare upgradable proxy, `Proxy.sol`
+    #[sol(rpc)]
+    interface IProxy {
+        function addImplementation(address newImplementation, bytes data, bool finalize) external;
+        function upgradeTo(address newImplementation, bytes data, bool finalize) external payable;
+        function implementation() external view returns (address);
+        function proxyNominateNewGovernor(address newGovernor) external;
+    }
+
+    /// Starknet core contract, `Starknet.sol`
+    #[sol(rpc)]
+    interface IStarknetCore {
+        function stateRoot() external view returns (uint256);
+        function stateBlockNumber() external view returns (int256);
+        function stateBlockHash() external view returns (uint256);
+        function programHash() external view returns (uint256);
+        function configHash() external view returns (uint256);
+        function registerOperator(address newOperator) external;
+        function isOperator(address user) external view returns (bool);
+        function starknetNominateNewGovernor(address newGovernor) external;
+    }
+}
+
+fn to_u256(value: Felt) -> U256 {
+    U256::from_be_bytes(value.to_bytes_be())
+}
+
+fn to_address(name: &str, value: Felt) -> Result<Address, Box<dyn Error>> {
+    let bytes = value.to_bytes_be();
+    if bytes[..12].iter().any(|byte| *byte != 0) {
+        return Err(format!("{name} 0x{:x} is not an Ethereum address", value).into());
+    }
+    Ok(Address::from_slice(&bytes[12..]))
+}
+
+/// Creation bytecode of a Foundry (`bytecode.object`) or Hardhat (`bytecode`) artifact
+fn load_bytecode(path: &Path) -> Result<Bytes, Box<dyn Error>> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read artifact {}: {e}", path.display()))?;
+    let artifact: Value = serde_json::from_str(&content)
+        .map_err(|e| format!("failed to parse artifact {}: {e}", path.display()))?;
+    let bytecode = match &artifact["bytecode"] {
+        Value::String(bytecode) => bytecode,
+        bytecode => bytecode["object"]
+            .as_str()
+            .ok_or_else(|| format!("no bytecode in artifact {}", path.display()))?,
+    };
+    if bytecode.contains("__") {
+        return Err(format!("artifact {} has unlinked libraries", path.display()).into());
+    }
+    hex::decode(bytecode)
+        .map(Bytes::from)
+        .map_err(|e| format!("invalid bytecode in artifact {}: {e}", path.display()).into())
+}
+
+/// Waits for the receipt of the transaction and fails if it reverted
+async fn confirm(
+    pending: PendingTransactionBuilder<Ethereum>,
+    description: &str,
+) -> Result<TransactionReceipt, Box<dyn Error>> {
+    let receipt = pending.get_receipt().await?;
+    if !receipt.status() {
+        return Err(format!(
+            "{description} reverted in transaction {}",
+            receipt.transaction_hash
+        )
+        .into());
+    }
+    Ok(receipt)
+}
+
+async fn deploy_contract(
+    provider: &DynProvider,
+    name: &str,
+    bytecode: Bytes,
+) -> Result<Address, Box<dyn Error>> {
+    let request = TransactionRequest::default().with_deploy_code(bytecode);
+    let pending = provider.send_transaction(request).await?;
+    let receipt = confirm(pending, &format!("deployment of {name}")).await?;
+    let address = receipt
+        .contract_address
+        .ok_or_else(|| format!("no contract address in the receipt of {name}"))?;
+    println!("{name} deployed at address: {address}");
+    Ok(address)
+}
+
+/// Connects to the Ethereum settlement chain with the configured signer and aborts if the
+/// chain id is not the expected one, or if it is mainnet and `allow_mainnet` is not set
+pub async fn connect(
+    settlement: &EthereumConfig,
+    allow_mainnet: bool,
+) -> Result<DynProvider, Box<dyn Error>> {
+    let signer = PrivateKeySigner::from_bytes(&settlement.secret_key)?;
+    println!("Settlement account: {}", signer.address());
+    let provider = ProviderBuilder::new()
+        .wallet(EthereumWallet::from(signer))
+        .connect_http(settlement.rpc_url.clone())
+        .erased();
+
+    let chain_id = provider.get_chain_id().await?;
+    if chain_id != settlement.chain_id {
+        return Err(format!(
+            "chain id mismatch: expected {}, node reports {}",
+            settlement.chain_id, chain_id
+        )
+        .into());
+    }
+    if chain_id == MAINNET_CHAIN_ID && !allow_mainnet {
+        return Err(
+            "refusing to send transactions to Ethereum mainnet without --allow-mainnet".into(),
+        );
+    }
+
+    Ok(provider)
+}
+
+/// Deploys `Starknet.sol` behind a StarkWare proxy, initializes it from the state of the L3
+/// block through `upgradeTo`, registers the operators and nominates the configured owner as
+/// governor of both. Returns the address of the proxy.
+pub async fn deploy_core_contract(
+    provider: &DynProvider,
+    core_contract: &CoreContractConfig,
+    state_root: Felt,
+    block_hash: Felt,
+) -> Result<Address, Box<dyn Error>> {
+    let ethereum = &core_contract.ethereum;
+    let (program_hash, config_hash) = core_contract
+        .program_info
+        .ok_or("core contract program_hash and config_hash must be set")?;
+    let verifier = to_address(
+        "core contract verifier",
+        ethereum
+            .verifier
+            .ok_or("core contract verifier must be set")?,
+    )?;
+    let operators = core_contract
+        .operators
+        .iter()
+        .map(|operator| to_address("core contract operator", *operator))
+        .collect::<Result<Vec<_>, _>>()?;
+    let owner = core_contract
+        .owner
+        .map(|owner| to_address("core contract owner", owner))
+        .transpose()?;
+    // artifacts are loaded before the first transaction
+    let implementation_bytecode = load_bytecode(&ethereum.core_contract_artifact)?;
+    let mut proxy_bytecode = load_bytecode(&ethereum.proxy_artifact)?.to_vec();
+    proxy_bytecode.extend(U256::from(ethereum.upgrade_delay).abi_encode());
+
+    let implementation = deploy_contract(
+        provider,
+        "Core contract implementation",
+        implementation_bytecode,
+    )
+    .await?;
+    let proxy_address =
+        deploy_contract(provider, "Core contract proxy", proxy_bytecode.into()).await?;
+
+    // no EIC, then the initial state as read by `Starknet.initializeContractState`
+    let mut words = vec![U256::ZERO, to_u256(program_hash)];
+    if let Some(aggregator_program_hash) = ethereum.aggregator_program_hash {
+        words.push(to_u256(aggregator_program_hash));
+    }
+    words.extend([
+        U256::from_be_slice(verifier.as_slice()),
+        to_u256(config_hash),
+        to_u256(state_root),
+        U256::from(core_contract.block_number),
+        to_u256(block_hash),
+    ]);
+    let init_data: Bytes = words
+        .iter()
+        .flat_map(|word| word.to_be_bytes::<32>())
+        .collect();
+
+    let proxy = IProxy::new(proxy_address, provider);
+    confirm(
+        proxy
+            .addImplementation(implementation, init_data.clone(), false)
+            .send()
+            .await?,
+        "addImplementation",
+    )
+    .await?;
+    confirm(
+        proxy
+            .upgradeTo(implementation, init_data, false)
+            .send()
+            .await?,
+        "upgradeTo",
+    )
+    .await?;
+    let active = proxy.implementation().call().await?;
+    if active != implementation {
+        return Err(format!(
+            "proxy {proxy_address} points to {active} after the upgrade, expected {implementation}"
+        )
+        .into());
+    }
+
+    let core = IStarknetCore::new(proxy_address, provider);
+    for operator in &operators {
+        confirm(
+            core.registerOperator(*operator).send().await?,
+            "registerOperator",
+        )
+        .await?;
+        if !core.isOperator(*operator).call().await? {
+            return Err(format!("{operator} is not an operator after its registration").into());
+        }
+        println!("Operator {operator} registered");
+    }
+
+    let state = (
+        core.stateRoot().call().await?,
+        core.stateBlockNumber().call().await?,
+        core.stateBlockHash().call().await?,
+        core.programHash().call().await?,
+        core.configHash().call().await?,
+    );
+    let expected = (
+        to_u256(state_root),
+        I256::try_from(core_contract.block_number)?,
+        to_u256(block_hash),
+        to_u256(program_hash),
+        to_u256(config_hash),
+    );
+    if state != expected {
+        return Err(format!(
+            "core contract initialized with {state:?}, expected {expected:?} \
+             (state root, block number, block hash, program hash, config hash)"
+        )
+        .into());
+    }
+
+    if let Some(owner) = owner {
+        confirm(
+            proxy.proxyNominateNewGovernor(owner).send().await?,
+            "proxyNominateNewGovernor",
+        )
+        .await?;
+        confirm(
+            core.starknetNominateNewGovernor(owner).send().await?,
+            "starknetNominateNewGovernor",
+        )
+        .await?;
+        println!(
+            "{owner} nominated as governor of the core contract, it has to call \
+             `proxyAcceptGovernance` and `starknetAcceptGovernance`"
+        );
+    }
+
+    Ok(proxy_address)
+}
@@ -0,0 +1,301 @@
+use axum::{
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use starknet::{
+    accounts::{Account, ConnectedAccount, ExecutionEncoding, SingleOwnerAccount},
+    core::types::{Felt, U256},
+    macros::selector,
+    providers::jsonrpc::JsonRpcClient,
+    signers::{LocalWallet, SigningKey},
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    net::{IpAddr, SocketAddr},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    account::execute_with_nonce,
+    amount::format_amount,
+    cli::{ConfigArgs, FaucetArgs},
+    config::{load_l3_config, TxSettings},
+    provider::FailoverTransport,
+    token::{balance_of, decimals, transfer_call},
+    utils::{call_string, check_chain_id},
+};
+
+type FaucetAccount =
+    SingleOwnerAccount<&'static JsonRpcClient<FailoverTransport>, &'static LocalWallet>;
+
+/// A token sent on each request
+struct FaucetToken {
+    symbol: String,
+    address: Felt,
+    amount: U256,
+    decimals: u8,
+}
+
+#[derive(Default)]
+struct RateLimits {
+    /// Last request accepted for each address
+    addresses: HashMap<Felt, Instant>,
+    /// Requests accepted from each IP during the current window
+    ips: HashMap<IpAddr, VecDeque<Instant>>,
+}
+
+struct Faucet {
+    account: FaucetAccount,
+    tokens: Vec<FaucetToken>,
+    tx: TxSettings,
+    /// Next nonce of the account, `None` until read from the node or after a failed send.
+    /// The lock is held while sending so concurrent requests get consecutive nonces.
+    nonce: tokio::sync::Mutex<Option<Felt>>,
+    limits: Mutex<RateLimits>,
+    address_cooldown: Duration,
+    ip_limit: usize,
+    ip_window: Duration,
+    drips: AtomicU64,
+}
+
+#[derive(Deserialize)]
+struct DripRequest {
+    address: String,
+}
+
+type ApiError = (StatusCode, Json<Value>);
+
+fn api_error(status: StatusCode, message: impl Into<String>) -> ApiError {
+    (status, Json(json!({ "error": message.into() })))
+}
+
+impl Faucet {
+    /// Accepts the request if neither the address nor the IP are over their limit
+    fn check_limits(&self, address: Felt, ip: IpAddr) -> Result<(), ApiError> {
+        let mut limits = self.limits.lock().unwrap();
+        let now = Instant::now();
+
+        if let Some(last) = limits.addresses.get(&address) {
+            let elapsed = now.duration_since(*last);
+            if elapsed < self.address_cooldown {
+                return Err(api_error(
+                    StatusCode::TOO_MANY_REQUESTS,
+                    format!(
+                        "0x{:x} was already funded, retry in {}s",
+                        address,
+                        (self.address_cooldown - elapsed).as_secs()
+                    ),
+                ));
+            }
+        }
+
+        let requests = limits.ips.entry(ip).or_default();
+        while requests
+            .front()
+            .is_some_and(|time| now.duration_since(*time) >= self.ip_window)
+        {
+            requests.pop_front();
+        }
+        if requests.len() >= self.ip_limit {
+            return Err(api_error(
+                StatusCode::TOO_MANY_REQUESTS,
+                format!(
+                    "too many requests from {ip}, at most {} every {}s",
+                    self.ip_limit,
+                    self.ip_window.as_secs()
+                ),
+            ));
+        }
+        requests.push_back(now);
+
+        let cooldown = self.address_cooldown;
+        limits
+            .addresses
+            .retain(|_, last| now.duration_since(*last) < cooldown);
+        limits.addresses.insert(address, now);
+
+        Ok(())
+    }
+
+    /// Sends every token to `recipient` in a single multicall, so that either all of them or
+    /// none are sent
+    async fn send(&self, recipient: Felt) -> Result<Felt, String> {
+        let mut next_nonce = self.nonce.lock().await;
+        let nonce = match *next_nonce {
+            Some(nonce) => nonce,
+            None => self.account.get_nonce().await.map_err(|e| e.to_string())?,
+        };
+
+        let calls = self
+            .tokens
+            .iter()
+            .map(|token| transfer_call(token.address, recipient, token.amount))
+            .collect();
+        match execute_with_nonce(&self.account, calls, self.tx, Some(nonce)).await {
+            Ok(result) => {
+                *next_nonce = Some(nonce + Felt::ONE);
+                Ok(result.transaction_hash)
+            }
+            Err(e) => {
+                // the nonce is read again from the node by the next request
+                *next_nonce = None;
+                Err(format!("failed to send the transfers: {e}"))
+            }
+        }
+    }
+}
+
+async fn drip(
+    State(faucet): State<Arc<Faucet>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    Json(request): Json<DripRequest>,
+) -> Result<Json<Value>, ApiError> {
+    let address = Felt::from_hex(request.address.trim())
+        .ok()
+        .filter(|address| *address != Felt::ZERO)
+        .ok_or_else(|| {
+            api_error(
+                StatusCode::BAD_REQUEST,
+                format!("invalid address `{}`", request.address),
+            )
+        })?;
+    faucet.check_limits(address, peer.ip())?;
+
+    let tx_hash = match faucet.send(address).await {
+        Ok(tx_hash) => tx_hash,
+        Err(e) => {
+            // nothing was sent, a failed request doesn't count against the address
+            faucet.limits.lock().unwrap().addresses.remove(&address);
+            println!("Faucet request for 0x{:x} failed: {e}", address);
+            return Err(api_error(StatusCode::INTERNAL_SERVER_ERROR, e));
+        }
+    };
+    faucet.drips.fetch_add(1, Ordering::Relaxed);
+    println!("Funded 0x{:x} from {}", address, peer.ip());
+
+    Ok(Json(json!({
+        "address": format!("0x{:x}", address),
+        "transaction_hash": format!("0x{:x}", tx_hash),
+    })))
+}
+
+async fn status(State(faucet): State<Arc<Faucet>>) -> Result<Json<Value>, ApiError> {
+    let mut tokens = vec![];
+    for token in &faucet.tokens {
+        let balance = balance_of(
+            faucet.account.provider(),
+            token.address,
+            faucet.account.address(),
+            faucet.account.block_id(),
+        )
+        .await
+        .map_err(|e| api_error(StatusCode::BAD_GATEWAY, e.to_string()))?;
+        tokens.push(json!({
+            "symbol": token.symbol,
+            "address": format!("0x{:x}", token.address),
+            "amount": format_amount(token.amount, token.decimals),
+            "balance": format_amount(balance, token.decimals),
+        }));
+    }
+
+    Ok(Json(json!({
+        "account": format!("0x{:x}", faucet.account.address()),
+        "tokens": tokens,
+        "drips": faucet.drips.load(Ordering::Relaxed),
+        "address_cooldown_secs": faucet.address_cooldown.as_secs(),
+        "ip_limit": faucet.ip_limit,
+        "ip_window_secs": faucet.ip_window.as_secs(),
+    })))
+}
+
+/// Runs the faucet until the process is stopped: `POST /drip` with `{"address": "0x..."}`
+/// sends the configured amounts, `GET /status` returns the balances of the faucet
+pub async fn faucet_command(args: &FaucetArgs, config: &ConfigArgs) -> Result<(), Box<dyn Error>> {
+    let config = load_l3_config(config)?;
+    let account_address = config
+        .l3
+        .account_address
+        .ok_or("ACCOUNT_ADDRESS must be set to run the faucet")?;
+    // the server runs until the process stops, the account can borrow them for good
+    let provider: &'static _ = Box::leak(Box::new(JsonRpcClient::new(FailoverTransport::new(
+        config.l3.rpc_endpoints.clone(),
+        config.l3.retry,
+    ))));
+    let chain_id = check_chain_id(provider, config.l3.chain_id, config.allow_mainnet).await?;
+    let signer: &'static _ = Box::leak(Box::new(LocalWallet::from(
+        SigningKey::from_secret_scalar(config.l3.secret_key),
+    )));
+    let mut account = SingleOwnerAccount::new(
+        provider,
+        signer,
+        account_address,
+        chain_id,
+        ExecutionEncoding::New,
+    );
+    account.set_block_id(config.l3.block_id);
+
+    let mut tokens = vec![];
+    for (address, amount) in [
+        (args.strk, &args.strk_amount),
+        (args.quaza, &args.quaza_amount),
+    ] {
+        let Some(address) = address else {
+            continue;
+        };
+        let block_id = account.block_id();
+        tokens.push(FaucetToken {
+            symbol: call_string(provider, address, selector!("symbol"), block_id).await?,
+            address,
+            amount: amount.to_token_units(provider, address, block_id).await?,
+            decimals: decimals(provider, address, block_id).await?,
+        });
+    }
+
+    let faucet = Arc::new(Faucet {
+        account,
+        tokens,
+        tx: config.l3.tx,
+        nonce: tokio::sync::Mutex::new(None),
+        limits: Mutex::new(RateLimits::default()),
+        address_cooldown: Duration::from_secs(args.address_cooldown),
+        ip_limit: args.ip_limit,
+        ip_window: Duration::from_secs(args.ip_window),
+        drips: AtomicU64::new(0),
+    });
+    for token in &faucet.tokens {
+        println!(
+            "Sending {} {} (0x{:x}) per request",
+            format_amount(token.amount, token.decimals),
+            token.symbol,
+            token.address
+        );
+    }
+
+    let app = Router::new()
+        .route("/drip", post(drip))
+        .route("/status", get(status))
+        .with_state(faucet);
+    let listener = tokio::net::TcpListener::bind(&args.listen)
+        .await
+        .map_err(|e| format!("failed to listen on {}: {e}", args.listen))?;
+    println!(
+        "Faucet of 0x{:x} listening on {}",
+        account_address, args.listen
+    );
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,282 @@
+use serde::Deserialize;
+use starknet::{
+    accounts::{Account, ConnectedAccount, SingleOwnerAccount},
+    core::types::{Felt, U256},
+    macros::selector,
+    providers::jsonrpc::JsonRpcClient,
+    signers::LocalWallet,
+};
+use std::{
+    collections::{btree_map::Entry, BTreeMap, BTreeSet},
+    error::Error,
+    fs,
+    path::Path,
+};
+
+use crate::{
+    account::execute,
+    amount::{checked_add, format_amount, Amount},
+    config::{expand_env, TxSettings},
+    provider::FailoverTransport,
+    token::{balance_of, decimals, mint_call, transfer_call},
+    utils::{call_string, wait_for_confirmation},
+    ws::WsClient,
+};
+
+/// Calls sent in a single multicall
+const BATCH_SIZE: usize = 25;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// Minted by the deployer, which must own the token
+    #[default]
+    Mint,
+    /// Transferred from the balance of the deployer
+    Transfer,
+}
+
+/// A line of the allocation file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AllocationConfig {
+    recipient: String,
+    token: String,
+    amount: String,
+    #[serde(default)]
+    action: Action,
+}
+
+/// Token of an allocation, either one deployed by the bootstrap or any address
+#[derive(Debug, Clone)]
+pub enum TokenRef {
+    Symbol(String),
+    Address(Felt),
+}
+
+#[derive(Debug, Clone)]
+pub struct Allocation {
+    pub recipient: Felt,
+    pub token: TokenRef,
+    pub amount: Amount,
+    pub action: Action,
+}
+
+/// 10 STRK and 10 QUAZA minted to the deployer, used without an allocation file
+pub fn default_allocations(deployer: Felt) -> Vec<Allocation> {
+    ["STRK", "QUAZA"]
+        .into_iter()
+        .map(|symbol| Allocation {
+            recipient: deployer,
+            token: TokenRef::Symbol(symbol.to_owned()),
+            amount: format!("10 {symbol}").parse().unwrap(),
+            action: Action::Mint,
+        })
+        .collect()
+}
+
+fn parse_allocation(line: usize, config: AllocationConfig) -> Result<Allocation, Box<dyn Error>> {
+    let recipient = expand_env(&config.recipient)?;
+    let recipient = Felt::from_hex(&recipient).map_err(|e| {
+        format!("allocation {line}: recipient {recipient} is not a valid felt: {e}")
+    })?;
+    let token = if config.token.starts_with("0x") {
+        TokenRef::Address(Felt::from_hex(&config.token).map_err(|e| {
+            format!(
+                "allocation {line}: token {} is not a valid felt: {e}",
+                config.token
+            )
+        })?)
+    } else {
+        TokenRef::Symbol(config.token)
+    };
+    let amount = config
+        .amount
+        .parse()
+        .map_err(|e| format!("allocation {line}: {e}"))?;
+
+    Ok(Allocation {
+        recipient,
+        token,
+        amount,
+        action: config.action,
+    })
+}
+
+/// Loads the allocations of a CSV file with a `recipient,token,amount[,action]` header, or
+/// of a JSON list of objects with the same fields when the extension is `.json`
+pub fn load_genesis(path: &Path) -> Result<Vec<Allocation>, Box<dyn Error>> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read genesis file {}: {e}", path.display()))?;
+    let configs: Vec<AllocationConfig> =
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => serde_json::from_str(&content).map_err(|e| e.to_string()),
+            _ => csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .comment(Some(b'#'))
+                .from_reader(content.as_bytes())
+                .deserialize()
+                .collect::<Result<_, _>>()
+                .map_err(|e| e.to_string()),
+        }
+        .map_err(|e| format!("failed to parse genesis file {}: {e}", path.display()))?;
+
+    configs
+        .into_iter()
+        .enumerate()
+        .map(|(index, config)| parse_allocation(index + 1, config))
+        .collect()
+}
+
+/// Totals of one token, for the summary
+#[derive(Debug)]
+struct TokenTotals {
+    symbol: String,
+    decimals: u8,
+    minted: U256,
+    transferred: U256,
+}
+
+/// Mints and transfers the allocations in batched multicalls, then checks the balance of
+/// every recipient and prints the totals per token.
+///
+/// `tokens` maps the symbols usable in the allocations to the tokens deployed by the bootstrap.
+pub async fn apply_genesis(
+    account: &SingleOwnerAccount<&JsonRpcClient<FailoverTransport>, &LocalWallet>,
+    ws: Option<&WsClient>,
+    allocations: &[Allocation],
+    tokens: &[(&str, Felt)],
+    tx: TxSettings,
+) -> Result<(), Box<dyn Error>> {
+    let provider = account.provider();
+    let block_id = account.block_id();
+    let deployer = account.address();
+
+    // every token and amount is resolved before the first transaction
+    let mut totals = BTreeMap::<Felt, TokenTotals>::new();
+    let mut transfers = vec![];
+    for allocation in allocations {
+        let token = match &allocation.token {
+            TokenRef::Address(address) => *address,
+            TokenRef::Symbol(symbol) => tokens
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(symbol))
+                .map(|(_, address)| *address)
+                .ok_or_else(|| format!("unknown token {symbol} in the genesis allocations"))?,
+        };
+        let token_totals = match totals.entry(token) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(TokenTotals {
+                symbol: call_string(provider, token, selector!("symbol"), block_id).await?,
+                decimals: decimals(provider, token, block_id).await?,
+                minted: U256::from(0u8),
+                transferred: U256::from(0u8),
+            }),
+        };
+
+        if let Some(symbol) = allocation.amount.symbol() {
+            if !token_totals.symbol.eq_ignore_ascii_case(symbol) {
+                return Err(format!(
+                    "amount {} is in {symbol} but token 0x{:x} is {}",
+                    allocation.amount, token, token_totals.symbol
+                )
+                .into());
+            }
+        }
+        let amount = allocation.amount.to_u256(token_totals.decimals)?;
+        let total = match allocation.action {
+            Action::Mint => &mut token_totals.minted,
+            Action::Transfer => &mut token_totals.transferred,
+        };
+        *total = checked_add(*total, amount)
+            .ok_or_else(|| format!("total allocation of {} overflows u256", token_totals.symbol))?;
+        transfers.push((token, allocation.recipient, amount, allocation.action));
+    }
+
+    // balances are compared as `before + received == after + sent`
+    let zero = (U256::from(0u8), U256::from(0u8));
+    let mut balances = BTreeMap::<(Felt, Felt), (U256, U256)>::new();
+    for (token, recipient, amount, action) in &transfers {
+        let overflow = || {
+            format!(
+                "total allocation of {} overflows u256",
+                totals[token].symbol
+            )
+        };
+        let received = &mut balances.entry((*token, *recipient)).or_insert(zero).0;
+        *received = checked_add(*received, *amount).ok_or_else(overflow)?;
+        if *action == Action::Transfer {
+            let sent = &mut balances.entry((*token, deployer)).or_insert(zero).1;
+            *sent = checked_add(*sent, *amount).ok_or_else(overflow)?;
+        }
+    }
+    let mut before = BTreeMap::new();
+    for (token, owner) in balances.keys() {
+        before.insert(
+            (*token, *owner),
+            balance_of(provider, *token, *owner, block_id).await?,
+        );
+    }
+
+    for batch in transfers.chunks(BATCH_SIZE) {
+        let calls = batch
+            .iter()
+            .map(|(token, recipient, amount, action)| match action {
+                Action::Mint => mint_call(*token, *recipient, *amount),
+                Action::Transfer => transfer_call(*token, *recipient, *amount),
+            })
+            .collect();
+        let tx_hash = execute(account, calls, tx).await?.transaction_hash;
+        wait_for_confirmation(provider, ws, tx_hash).await?;
+        println!(
+            "Genesis batch of {} allocations sent: 0x{:x}",
+            batch.len(),
+            tx_hash
+        );
+    }
+
+    let mut mismatches = vec![];
+    for ((token, owner), (received, sent)) in &balances {
+        let balance = balance_of(provider, *token, *owner, block_id).await?;
+        let expected = checked_add(before[&(*token, *owner)], *received);
+        if expected.is_none() || expected != checked_add(balance, *sent) {
+            let token_totals = &totals[token];
+            mismatches.push(format!(
+                "balance of 0x{:x} is {} {}, expected {} + {} - {}",
+                owner,
+                format_amount(balance, token_totals.decimals),
+                token_totals.symbol,
+                format_amount(before[&(*token, *owner)], token_totals.decimals),
+                format_amount(*received, token_totals.decimals),
+                format_amount(*sent, token_totals.decimals),
+            ));
+        }
+    }
+    if !mismatches.is_empty() {
+        return Err(format!(
+            "genesis allocations not applied as expected:\n  {}",
+            mismatches.join("\n  ")
+        )
+        .into());
+    }
+
+    println!("Genesis allocations: {}", allocations.len());
+    for (token, token_totals) in &totals {
+        let recipients = transfers
+            .iter()
+            .filter(|(allocation_token, ..)| allocation_token == token)
+            .map(|(_, recipient, ..)| recipient)
+            .collect::<BTreeSet<_>>()
+            .len();
+        println!(
+            "  {} (0x{:x}): {} minted, {} transferred to {} recipients",
+            token_totals.symbol,
+            token,
+            format_amount(token_totals.minted, token_totals.decimals),
+            format_amount(token_totals.transferred, token_totals.decimals),
+            recipients
+        );
+    }
+
+    Ok(())
+}
@@ -1,88 +1,226 @@
 mod account;
+mod amount;
+mod cli;
+mod compat;
 mod config;
+mod core_contract;
 mod declare;
 mod deploy;
+mod ethereum;
+mod faucet;
+mod genesis;
+mod migration;
+mod ownership;
+mod preflight;
+mod provider;
+mod storage;
 mod token;
+mod upgrade;
 mod utils;
+mod ws;
 
-use config::STRK_SALT;
+use clap::Parser;
+use config::{Settlement, TxSettings, TxVersion, ETH_ADDRESS, STRK_SALT};
+use provider::FailoverTransport;
 use starknet::{
     accounts::{
-        Account, AccountFactory, ConnectedAccount, ExecutionEncoding, OpenZeppelinAccountFactory,
-        SingleOwnerAccount,
+        ConnectedAccount, ExecutionEncoding, OpenZeppelinAccountFactory, SingleOwnerAccount,
     },
-    core::types::{
-        BlockId, BlockTag, BlockWithTxHashes, Call, DeclareTransactionResult,
-        DeployAccountTransactionResult, Felt, MaybePendingBlockWithTxHashes, U256,
+    core::{
+        types::{Call, Felt},
+        utils::get_contract_address,
     },
     macros::selector,
-    providers::{
-        jsonrpc::{HttpTransport, JsonRpcClient},
-        Provider, Url,
-    },
-    signers::{LocalWallet, Signer, SigningKey},
+    providers::{jsonrpc::JsonRpcClient, Provider},
+    signers::{LocalWallet, SigningKey},
 };
-use utils::wait_for_confirmation;
+use utils::{check_chain_id, confirm_declaration, format_chain_id, wait_for_block};
 
 #[tokio::main]
 async fn main() {
-    let config = config::load_config();
-    let signing_key = SigningKey::from_secret_scalar(config.deployer_secret_key);
+    let cli = cli::Cli::parse();
+    match &cli.command {
+        Some(cli::Command::Storage(args)) => {
+            storage::print_storage(args, &cli.config).await.unwrap();
+            return;
+        }
+        Some(cli::Command::Upgrade(args)) => {
+            upgrade::upgrade_command(args, &cli.config).await.unwrap();
+            return;
+        }
+        Some(cli::Command::Faucet(args)) => {
+            faucet::faucet_command(args, &cli.config).await.unwrap();
+            return;
+        }
+        None => {}
+    }
+
+    let config = config::load_config(&cli.config).unwrap();
+    if let Some(profile) = &config.l3.profile {
+        println!("Using profile: {}", profile);
+    }
+    let rpc_admin = config.l3.rpc_admin.as_ref().unwrap();
+    let signing_key = SigningKey::from_secret_scalar(config.l3.secret_key);
     let signer = LocalWallet::from(signing_key);
-    let public_key = signer.get_public_key().await.unwrap().scalar();
 
-    let provider = JsonRpcClient::new(HttpTransport::new(Url::parse(&config.rpc_url).unwrap()));
+    let provider = JsonRpcClient::new(FailoverTransport::new(
+        config.l3.rpc_endpoints.clone(),
+        config.l3.retry,
+    ));
 
-    let chain_id = provider.chain_id().await.unwrap();
-    println!(
-        "Chain ID: {}",
-        String::from_utf8_lossy(&chain_id.to_bytes_be())
+    preflight::wait_for_node(
+        &provider,
+        rpc_admin,
+        config.l3.tx.version,
+        config.ready_timeout,
+    )
+    .await
+    .unwrap();
+
+    // both chains are checked upfront so that a misconfigured settlement chain
+    // doesn't go unnoticed until the end of the bootstrap
+    let chain_id = check_chain_id(&provider, config.l3.chain_id, config.allow_mainnet)
+        .await
+        .unwrap();
+    println!("Chain ID: {}", format_chain_id(chain_id));
+    let (starknet_settlement, ethereum_settlement) = match &config.settlement {
+        Settlement::Starknet(settlement) => {
+            let settlement_provider = JsonRpcClient::new(FailoverTransport::new(
+                settlement.rpc_endpoints.clone(),
+                settlement.retry,
+            ));
+            let settlement_chain_id = check_chain_id(
+                &settlement_provider,
+                settlement.chain_id,
+                config.allow_mainnet,
+            )
+            .await
+            .unwrap();
+            println!(
+                "Settlement chain ID: {}",
+                format_chain_id(settlement_chain_id)
+            );
+            let settlement_spec = preflight::check_spec(
+                &settlement_provider.spec_version().await.unwrap(),
+                settlement.tx.version,
+            )
+            .unwrap();
+            println!("Settlement RPC spec: {}", settlement_spec);
+            core_contract::check_core_contract_class(
+                &settlement_provider,
+                settlement,
+                &config.core_contract,
+            )
+            .await
+            .unwrap();
+            (Some((settlement_provider, settlement_chain_id)), None)
+        }
+        Settlement::Ethereum(settlement) => {
+            let settlement_provider = ethereum::connect(settlement, config.allow_mainnet)
+                .await
+                .unwrap();
+            println!("Settlement chain ID: {} (Ethereum)", settlement.chain_id);
+            (None, Some(settlement_provider))
+        }
+    };
+
+    let ws = ws::connect_or_poll(config.l3.ws.as_ref()).await;
+
+    // the account deploying STRK and ETH, predicted so that the migrations can refer to it
+    let account_salt = config
+        .salts
+        .get("account", Felt::from_hex_unchecked("0x01"));
+    let predicted_account_address = get_contract_address(
+        account_salt,
+        account::sierra_class_hash("./contracts/account/contract_class.json").unwrap(),
+        &[SigningKey::from_secret_scalar(config.l3.secret_key)
+            .verifying_key()
+            .scalar()],
+        Felt::ZERO,
     );
+    let migration_vars = [("BOOTSTRAP_ACCOUNT", predicted_account_address)];
+
+    // loaded upfront so that a broken migration file fails before any transaction
+    let strk_migration =
+        migration::load_migration(&config.strk_migration, &migration_vars).unwrap();
+    // the ETH proxy has to land on the Starknet address, which is checked before any transaction
+    let eth = config
+        .eth_salt
+        .map(|salt| -> Result<_, Box<dyn std::error::Error>> {
+            account::check_legacy_address(
+                "./contracts/EthOrigin.json",
+                salt,
+                &[Felt::ZERO],
+                ETH_ADDRESS,
+            )?;
+            Ok((
+                salt,
+                migration::load_migration(&config.eth_migration, &migration_vars)?,
+            ))
+        })
+        .transpose()
+        .unwrap();
+    let genesis_allocations = config
+        .l3
+        .genesis
+        .as_deref()
+        .map(genesis::load_genesis)
+        .transpose()
+        .unwrap();
 
-    let DeclareTransactionResult {
-        transaction_hash: tx_hash,
-        class_hash: udc_class_hash,
-    } = declare::declare_v0(
-        &config.rpc_admin_url,
-        "./contracts/UDC.json",
-        Felt::from(0x01),
+    let udc_class_hash = confirm_declaration(
+        &provider,
+        ws.as_ref(),
+        "UDC",
+        declare::declare_v0_if_missing(
+            rpc_admin,
+            &provider,
+            config.l3.block_id,
+            "./contracts/UDC.json",
+            Felt::from(0x01),
+        )
+        .await
+        .unwrap(),
     )
     .await
     .unwrap();
-    wait_for_confirmation(&provider, tx_hash).await.unwrap();
-    println!("UDC declared with class hash: 0x{:x}", udc_class_hash);
-
-    let DeclareTransactionResult {
-        transaction_hash: tx_hash,
-        class_hash: oz_v0_deploy_class_hash,
-    } = declare::declare_v0(
-        &config.rpc_admin_url,
-        "./contracts/DeployerAccountSepolia.json",
-        Felt::from(0x01),
+
+    let oz_v0_deploy_class_hash = confirm_declaration(
+        &provider,
+        ws.as_ref(),
+        "account V0 with deploy",
+        declare::declare_v0_if_missing(
+            rpc_admin,
+            &provider,
+            config.l3.block_id,
+            "./contracts/DeployerAccountSepolia.json",
+            Felt::from(0x01),
+        )
+        .await
+        .unwrap(),
     )
     .await
     .unwrap();
-    wait_for_confirmation(&provider, tx_hash).await.unwrap();
-    println!(
-        "account V0 with deploy declared with class hash: 0x{:x}",
-        oz_v0_deploy_class_hash
-    );
 
     let account_factory =
         OpenZeppelinAccountFactory::new(oz_v0_deploy_class_hash, chain_id, &signer, &provider)
             .await
             .unwrap();
-    let DeployAccountTransactionResult {
-        transaction_hash: tx_hash,
-        contract_address: oz_v0_deploy_address,
-    } = account_factory
-        .deploy_v1(Felt::from_hex_unchecked("0x01")) // salt
-        .max_fee(Felt::ZERO)
-        .nonce(Felt::ZERO)
-        .send()
-        .await
-        .unwrap();
-    wait_for_confirmation(&provider, tx_hash).await.unwrap();
+    // the Cairo 0 deployer account can only be deployed and used with v1 transactions
+    let legacy_tx = TxSettings {
+        version: TxVersion::V1,
+        ..config.l3.tx
+    };
+    let oz_v0_deploy_address = account::deploy_account(
+        &account_factory,
+        ws.as_ref(),
+        config
+            .salts
+            .get("deployer_account", Felt::from_hex_unchecked("0x01")),
+        legacy_tx,
+    )
+    .await
+    .unwrap();
     println!("Deployed account at address: 0x{:x}", oz_v0_deploy_address);
 
     let mut account = SingleOwnerAccount::new(
@@ -92,67 +230,62 @@ async fn main() {
         chain_id,
         ExecutionEncoding::Legacy,
     );
-    account.set_block_id(BlockId::Tag(BlockTag::Pending));
+    account.set_block_id(config.l3.block_id);
 
-    let (tx_hash, udc_address) = deploy::deploy(
-        &account,
-        udc_class_hash,
-        Felt::from_hex_unchecked("0x00"), // salt
-        &[],
-    )
-    .await
-    .unwrap();
-    wait_for_confirmation(&provider, tx_hash).await.unwrap();
+    let udc_address =
+        deploy::deploy_canonical_udc(&account, ws.as_ref(), udc_class_hash, legacy_tx)
+            .await
+            .unwrap();
     println!("Deployed UDC at address: 0x{:x}", udc_address);
 
     // declare account class v1 for the deployer
-    let DeclareTransactionResult {
-        transaction_hash: tx_hash,
-        class_hash: account_class_hash,
-    } = account::declare_v2(
-        &account,
-        "./contracts/account/contract_class.json",
-        "./contracts/account/compiled_contract_class.json",
-        true,
+    let account_class_hash = confirm_declaration(
+        &provider,
+        ws.as_ref(),
+        "Account",
+        account::declare_v2_if_missing(
+            &account,
+            "./contracts/account/contract_class.json",
+            "./contracts/account/compiled_contract_class.json",
+            config.l3.tx,
+        )
+        .await
+        .unwrap(),
     )
     .await
     .unwrap();
-    wait_for_confirmation(&provider, tx_hash).await.unwrap();
-    println!(
-        "Account declared with class hash: 0x{:x}",
-        account_class_hash
-    );
 
-    let DeclareTransactionResult {
-        transaction_hash: tx_hash,
-        class_hash: eic_class_hash,
-    } = account::declare_v2(
-        &account,
-        "./contracts/eic/contract_class.json",
-        "./contracts/eic/compiled_contract_class.json",
-        true,
+    let eic_class_hash = confirm_declaration(
+        &provider,
+        ws.as_ref(),
+        "EIC class",
+        account::declare_v2_if_missing(
+            &account,
+            "./contracts/eic/contract_class.json",
+            "./contracts/eic/compiled_contract_class.json",
+            config.l3.tx,
+        )
+        .await
+        .unwrap(),
     )
     .await
     .unwrap();
-    wait_for_confirmation(&provider, tx_hash).await.unwrap();
-    println!("EIC class declared with class hash: 0x{:x}", eic_class_hash);
 
     let account_factory =
         OpenZeppelinAccountFactory::new(account_class_hash, chain_id, &signer, &provider)
             .await
             .unwrap();
-    let DeployAccountTransactionResult {
-        transaction_hash: tx_hash,
-        contract_address: account_address,
-    } = account_factory
-        .deploy_v1(Felt::from_hex_unchecked("0x01")) // salt
-        .max_fee(Felt::ZERO)
-        .nonce(Felt::ZERO)
-        .send()
-        .await
-        .unwrap();
-    wait_for_confirmation(&provider, tx_hash).await.unwrap();
+    let account_address =
+        account::deploy_account(&account_factory, ws.as_ref(), account_salt, config.l3.tx)
+            .await
+            .unwrap();
     println!("Deployed account at address: 0x{:x}", account_address);
+    if account_address != predicted_account_address {
+        panic!(
+            "account deployed at 0x{:x}, the migrations were loaded with 0x{:x}",
+            account_address, predicted_account_address
+        );
+    }
     let mut account = SingleOwnerAccount::new(
         &provider,
         &signer,
@@ -160,177 +293,259 @@ async fn main() {
         chain_id,
         ExecutionEncoding::New,
     );
-    account.set_block_id(BlockId::Tag(BlockTag::Pending));
+    account.set_block_id(config.l3.block_id);
 
-    let DeclareTransactionResult {
-        transaction_hash: tx_hash,
-        class_hash: strk_class_hash,
-    } = account::declare_v1(&account, "./contracts/StrkOrigin.json", true)
-        .await
-        .unwrap();
-    wait_for_confirmation(&provider, tx_hash).await.unwrap();
-    println!(
-        "Original STRK class declared with class hash: 0x{:x}",
-        strk_class_hash
-    );
+    let strk_class_hash = confirm_declaration(
+        &provider,
+        ws.as_ref(),
+        "Original STRK class",
+        account::declare_v1_if_missing(&account, "./contracts/StrkOrigin.json", config.l3.tx)
+            .await
+            .unwrap(),
+    )
+    .await
+    .unwrap();
 
-    let DeclareTransactionResult {
-        transaction_hash: tx_hash,
-        class_hash: token_class_hash,
-    } = account::declare_v2(
-        &account,
-        "./contracts/token/contract_class.json",
-        "./contracts/token/compiled_contract_class.json",
-        true,
+    let token_class_hash = confirm_declaration(
+        &provider,
+        ws.as_ref(),
+        "Token",
+        account::declare_v2_if_missing(
+            &account,
+            "./contracts/token/contract_class.json",
+            "./contracts/token/compiled_contract_class.json",
+            config.l3.tx,
+        )
+        .await
+        .unwrap(),
     )
     .await
     .unwrap();
-    wait_for_confirmation(&provider, tx_hash).await.unwrap();
-    println!("Token declared with class hash: 0x{:x}", token_class_hash);
 
-    let DeclareTransactionResult {
-        transaction_hash: tx_hash,
-        class_hash: argent_class_hash,
-    } = account::declare_v2(
-        &account,
-        "./contracts/argent/contract_class.json",
-        "./contracts/argent/compiled_contract_class.json",
-        true,
+    confirm_declaration(
+        &provider,
+        ws.as_ref(),
+        "Argent account",
+        account::declare_v2_if_missing(
+            &account,
+            "./contracts/argent/contract_class.json",
+            "./contracts/argent/compiled_contract_class.json",
+            config.l3.tx,
+        )
+        .await
+        .unwrap(),
     )
     .await
     .unwrap();
-    wait_for_confirmation(&provider, tx_hash).await.unwrap();
-    println!(
-        "Argent account declared with class hash: 0x{:x}",
-        argent_class_hash
-    );
 
     // deploy STRK with the same salt and class hash as the original STRK class to get the same address
     // it's necessary to deploy the STRK contract directly from the deployer account without UDC to be
     // governor of the STRK contract
-    let (tx_hash, strk_token_address) =
-        account::deploy(&account, strk_class_hash, STRK_SALT, &[Felt::ZERO], true)
+    let strk_token_address = account::deploy(
+        &account,
+        ws.as_ref(),
+        strk_class_hash,
+        STRK_SALT,
+        &[Felt::ZERO],
+        config.l3.tx,
+    )
+    .await
+    .unwrap();
+    println!("Token STRK deployed at address: 0x{:x}", strk_token_address);
+
+    upgrade::upgrade_proxy(
+        &account,
+        ws.as_ref(),
+        strk_token_address,
+        &upgrade::ProxyUpgrade {
+            implementation: token_class_hash,
+            eic: Some(eic_class_hash), // migrates the storage of the original STRK
+            init_vector: strk_migration.init_vector(),
+            is_final: false,
+        },
+        config.l3.tx,
+    )
+    .await
+    .unwrap();
+    println!("STRK class hash successfully updated");
+    migration::verify_migration(
+        &provider,
+        strk_token_address,
+        &strk_migration,
+        config.l3.block_id,
+    )
+    .await
+    .unwrap();
+    println!("STRK storage migration verified");
+
+    // same as STRK for the ETH fee token, with its own storage migration
+    let eth_token = match &eth {
+        Some((eth_salt, eth_migration)) => {
+            let eth_class_hash = confirm_declaration(
+                &provider,
+                ws.as_ref(),
+                "Original ETH class",
+                account::declare_v1_if_missing(
+                    &account,
+                    "./contracts/EthOrigin.json",
+                    config.l3.tx,
+                )
+                .await
+                .unwrap(),
+            )
             .await
             .unwrap();
-    wait_for_confirmation(&provider, tx_hash).await.unwrap();
-    println!("Token STRK deployed at address: 0x{:x}", strk_token_address);
 
-    let calldata = vec![
-        token_class_hash, // new class hash
-        eic_class_hash,   // class hash of the updater storage
-        Felt::from(0x00), // init vector length and data for EIC
-        Felt::from(0x00), // is the final implementation
-    ];
-    let call = Call {
-        to: strk_token_address,
-        selector: selector!("add_implementation"),
-        calldata: calldata.clone(),
-    };
-    let tx_hash = account
-        .execute_v1(vec![call])
-        .max_fee(Felt::ZERO)
-        .send()
-        .await
-        .unwrap()
-        .transaction_hash;
-    wait_for_confirmation(&provider, tx_hash).await.unwrap();
+            let eth_token_address = account::deploy(
+                &account,
+                ws.as_ref(),
+                eth_class_hash,
+                *eth_salt,
+                &[Felt::ZERO],
+                config.l3.tx,
+            )
+            .await
+            .unwrap();
+            println!("Token ETH deployed at address: 0x{:x}", eth_token_address);
 
-    let call = Call {
-        to: strk_token_address,
-        selector: selector!("upgrade_to"),
-        calldata,
+            upgrade::upgrade_proxy(
+                &account,
+                ws.as_ref(),
+                eth_token_address,
+                &upgrade::ProxyUpgrade {
+                    implementation: token_class_hash,
+                    eic: Some(eic_class_hash),
+                    init_vector: eth_migration.init_vector(),
+                    is_final: false,
+                },
+                config.l3.tx,
+            )
+            .await
+            .unwrap();
+            migration::verify_migration(
+                &provider,
+                eth_token_address,
+                eth_migration,
+                config.l3.block_id,
+            )
+            .await
+            .unwrap();
+            println!("ETH upgraded and storage migration verified");
+
+            Some((eth_token_address, eth_class_hash))
+        }
+        None => {
+            println!("No ETH salt set, the ETH fee token is not deployed");
+            None
+        }
     };
-    let tx_hash = account
-        .execute_v1(vec![call])
-        .max_fee(Felt::ZERO)
-        .send()
-        .await
-        .unwrap()
-        .transaction_hash;
-    wait_for_confirmation(&provider, tx_hash).await.unwrap();
-    println!("STRK class hash successfully updated");
 
-    let (tx_hash, quaza_token_address) = token::deploy_token(
+    let quaza_token_address = deploy::deploy_udc(
         &account,
+        ws.as_ref(),
         token_class_hash,
-        "Quaza Token",
-        "QUAZA",
-        account_address,
-        Felt::from_hex_unchecked("0x02"),
+        config.salts.get("quaza", Felt::from_hex_unchecked("0x02")),
+        &token::constructor_call_data("Quaza Token", "QUAZA", account_address),
+        config.l3.udc,
+        config.l3.tx,
     )
     .await
     .unwrap();
-    wait_for_confirmation(&provider, tx_hash).await.unwrap();
     println!(
         "Token QUAZA deployed at address: 0x{:x}",
         quaza_token_address
     );
 
-    let one_token: U256 = U256::from(10u128.pow(18));
-
-    let tx_hash = token::mint(
-        &account,
-        &strk_token_address,
-        &account_address,
-        &(U256::from(10u128) * one_token),
-    )
-    .await
-    .unwrap();
-    wait_for_confirmation(&provider, tx_hash).await.unwrap();
-    println!("Minted 10 STRK tokens");
+    let mut tokens = vec![("STRK", strk_token_address), ("QUAZA", quaza_token_address)];
+    if let Some((eth_token_address, _)) = eth_token {
+        tokens.push(("ETH", eth_token_address));
+    }
+    let allocations =
+        genesis_allocations.unwrap_or_else(|| genesis::default_allocations(account_address));
+    genesis::apply_genesis(&account, ws.as_ref(), &allocations, &tokens, config.l3.tx)
+        .await
+        .unwrap();
 
-    let tx_hash = token::mint(
-        &account,
-        &quaza_token_address,
-        &account_address,
-        &(U256::from(10u128) * one_token),
-    )
-    .await
-    .unwrap();
-    wait_for_confirmation(&provider, tx_hash).await.unwrap();
-    println!("Minted 10 QUAZA tokens");
-
-    let (block_hash, state_root) = loop {
-        match provider.get_block_with_tx_hashes(BlockId::Number(0)).await {
-            Ok(MaybePendingBlockWithTxHashes::Block(BlockWithTxHashes {
-                block_hash,
-                new_root,
-                ..
-            })) => break (block_hash, new_root),
-            _ => {
-                println!("Waiting for block 0 finalization...");
-                tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+    // the deployer key must not keep the mint rights on production networks
+    match config.l3.governance_address {
+        Some(governance_address) => {
+            for (name, token_address) in &tokens {
+                ownership::transfer_ownership(
+                    &account,
+                    ws.as_ref(),
+                    *token_address,
+                    governance_address,
+                    config.l3.tx,
+                )
+                .await
+                .unwrap();
+                println!(
+                    "{} ownership transferred to 0x{:x}",
+                    name, governance_address
+                );
+            }
+            // the proxy governors are only left if its class wasn't replaced by the upgrade
+            let mut proxies = vec![(strk_token_address, strk_class_hash)];
+            proxies.extend(eth_token);
+            for (proxy_address, proxy_class_hash) in proxies {
+                let class_hash = provider
+                    .get_class_hash_at(config.l3.block_id, proxy_address)
+                    .await
+                    .unwrap();
+                if class_hash == proxy_class_hash {
+                    ownership::nominate_proxy_governor(
+                        &account,
+                        ws.as_ref(),
+                        proxy_address,
+                        governance_address,
+                        config.l3.tx,
+                    )
+                    .await
+                    .unwrap();
+                }
             }
         }
-    };
-    println!("Block 0 hash: 0x{:x}", block_hash);
+        None => {
+            println!("No governance address set, the deployer keeps the ownership of the tokens")
+        }
+    }
 
-    let DeclareTransactionResult {
-        transaction_hash: tx_hash,
-        class_hash: counter_class_hash,
-    } = account::declare_v2(
-        &account,
-        "./contracts/counter/contract_class.json",
-        "./contracts/counter/compiled_contract_class.json",
-        true,
+    let (block_hash, state_root) =
+        wait_for_block(&provider, ws.as_ref(), config.core_contract.block_number)
+            .await
+            .unwrap();
+    println!(
+        "Block {} hash: 0x{:x}",
+        config.core_contract.block_number, block_hash
+    );
+
+    let counter_class_hash = confirm_declaration(
+        &provider,
+        ws.as_ref(),
+        "Counter",
+        account::declare_v2_if_missing(
+            &account,
+            "./contracts/counter/contract_class.json",
+            "./contracts/counter/compiled_contract_class.json",
+            config.l3.tx,
+        )
+        .await
+        .unwrap(),
     )
     .await
     .unwrap();
-    wait_for_confirmation(&provider, tx_hash).await.unwrap();
-    println!(
-        "Counter declared with class hash: 0x{:x}",
-        counter_class_hash
-    );
-    let (tx_hash, counter_address) = account::deploy(
+    let counter_address = account::deploy(
         &account,
+        ws.as_ref(),
         counter_class_hash,
-        Felt::from_hex_unchecked("0x00"), // salt
+        config
+            .salts
+            .get("counter", Felt::from_hex_unchecked("0x00")),
         &[],
-        true,
+        config.l3.tx,
     )
     .await
     .unwrap();
-    wait_for_confirmation(&provider, tx_hash).await.unwrap();
     println!("Counter deployed at address: 0x{:x}", counter_address);
     let call = Call {
         to: counter_address,
@@ -339,14 +554,14 @@ async fn main() {
     };
     let nonce = account.get_nonce().await.unwrap();
     for i in 0..10 {
-        let tx_hash = account
-            .execute_v1(vec![call.clone()])
-            .max_fee(Felt::ZERO)
-            .nonce(nonce + Felt::from(i))
-            .send()
-            .await
-            .unwrap()
-            .transaction_hash;
+        account::execute_with_nonce(
+            &account,
+            vec![call.clone()],
+            config.l3.tx,
+            Some(nonce + Felt::from(i)),
+        )
+        .await
+        .unwrap();
     }
     println!("Counter incremented 10 times");
 
@@ -357,40 +572,53 @@ async fn main() {
         return;
     }
 
+    let Settlement::Starknet(settlement) = &config.settlement else {
+        println!("Deploying core contract on Ethereum...");
+        let core_contract_address = ethereum::deploy_core_contract(
+            ethereum_settlement.as_ref().unwrap(),
+            &config.core_contract,
+            state_root,
+            block_hash,
+        )
+        .await
+        .unwrap();
+        println!(
+            "Core contract deployed at address: {}",
+            core_contract_address
+        );
+        return;
+    };
+
     // deploy starknet core contract
     println!("Deploying core contract...");
-    let provider = JsonRpcClient::new(HttpTransport::new(
-        Url::parse(&config.rpc_starknet_url).unwrap(),
-    ));
-    let chain_id = provider.chain_id().await.unwrap();
+    let settlement_account_address = settlement.account_address.unwrap();
+    let (provider, settlement_chain_id) = starknet_settlement.unwrap();
+    // the node behind the URL may have changed while bootstrapping
+    let chain_id = check_chain_id(&provider, settlement_chain_id, config.allow_mainnet)
+        .await
+        .unwrap();
+    let signer = LocalWallet::from(SigningKey::from_secret_scalar(settlement.secret_key));
     let mut account = SingleOwnerAccount::new(
         &provider,
         &signer,
-        config.starknet_account_address,
+        settlement_account_address,
         chain_id,
         ExecutionEncoding::New,
     );
-    account.set_block_id(BlockId::Tag(BlockTag::Pending));
+    account.set_block_id(settlement.block_id);
+    let ws = ws::connect_or_poll(settlement.ws.as_ref()).await;
 
-    let core_contract_class_hash = Felt::from_hex_unchecked(
-        "0x07e32e97ad7d1809358418ec553d61d0f537fba13d5b8ac3aa479ec9c632ef95",
-    );
-    let calldata = vec![
-        config.starknet_account_address, // owner
+    let core_contract_address = core_contract::deploy_core_contract(
+        &account,
+        ws.as_ref(),
+        settlement,
+        &config.core_contract,
+        &config.salts,
         state_root,
-        Felt::from(0x00), // block_number
         block_hash,
-    ];
-    let (tx_hash, core_contract_address) = deploy::deploy_v1(
-        &account,
-        core_contract_class_hash,
-        Felt::from_hex_unchecked("0x00"), // salt
-        &calldata,
-        false,
     )
     .await
     .unwrap();
-    wait_for_confirmation(&provider, tx_hash).await.unwrap();
     println!(
         "Core contract deployed at address: 0x{:x}",
         core_contract_address
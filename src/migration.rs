@@ -0,0 +1,247 @@
+use serde::Deserialize;
+use starknet::{
+    core::types::{BlockId, Felt, U256},
+    macros::selector,
+    providers::{jsonrpc::JsonRpcClient, Provider},
+};
+use std::{collections::HashSet, error::Error, fs, path::Path};
+
+use crate::{
+    config::{expand_env, parse_felt_or_short_string},
+    provider::FailoverTransport,
+    storage::storage_address,
+    utils::{call_string, call_view},
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StorageWriteConfig {
+    key: String,
+    value: String,
+}
+
+/// Storage migration file, keys are hex felts or storage paths and values hex felts or short
+/// strings
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MigrationFile {
+    /// Keys set back to zero
+    #[serde(default)]
+    clear: Vec<String>,
+    #[serde(default)]
+    write: Vec<StorageWriteConfig>,
+    #[serde(default)]
+    expect: ExpectConfig,
+}
+
+/// Values returned by the ERC20 getters once the migration is applied
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExpectConfig {
+    name: Option<String>,
+    symbol: Option<String>,
+    decimals: Option<u8>,
+    total_supply: Option<String>,
+    owner: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct ExpectedToken {
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub decimals: Option<u8>,
+    pub total_supply: Option<U256>,
+    pub owner: Option<Felt>,
+}
+
+/// Storage writes applied by the EIC, in order
+#[derive(Debug)]
+pub struct StorageMigration {
+    pub writes: Vec<(Felt, Felt)>,
+    pub expect: ExpectedToken,
+}
+
+impl StorageMigration {
+    /// `eic_init_data` of the generic EIC, a flat list of (key, value) pairs
+    pub fn init_vector(&self) -> Vec<Felt> {
+        self.writes
+            .iter()
+            .flat_map(|(key, value)| [*key, *value])
+            .collect()
+    }
+}
+
+/// Replaces the `${NAME}` references of `value` with the given variables, then with env vars
+fn expand(value: &str, vars: &[(&str, Felt)]) -> Result<String, Box<dyn Error>> {
+    let value = vars.iter().fold(value.to_owned(), |value, (name, var)| {
+        value.replace(&format!("${{{name}}}"), &format!("0x{:x}", var))
+    });
+    expand_env(&value)
+}
+
+/// Parses a hex key, or computes the address of a storage path like `ERC20_balances[0x123]`
+fn parse_key(key: &str, vars: &[(&str, Felt)]) -> Result<Felt, Box<dyn Error>> {
+    let key = expand(key, vars)?;
+    if key.starts_with("0x") {
+        Felt::from_hex(&key)
+            .map_err(|e| format!("storage key {key} is not a valid felt: {e}").into())
+    } else {
+        storage_address(&key)
+    }
+}
+
+/// Loads a migration from a TOML file, or a JSON one with the `.json` extension. `vars` are
+/// the values of the `${NAME}` references known to the bootstrap, other references are read
+/// from the environment.
+pub fn load_migration(
+    path: &Path,
+    vars: &[(&str, Felt)],
+) -> Result<StorageMigration, Box<dyn Error>> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read migration file {}: {e}", path.display()))?;
+    let file: MigrationFile = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => serde_json::from_str(&content).map_err(|e| e.to_string()),
+        _ => toml::from_str(&content).map_err(|e| e.to_string()),
+    }
+    .map_err(|e| format!("failed to parse migration file {}: {e}", path.display()))?;
+
+    let mut writes = file
+        .clear
+        .iter()
+        .map(|key| Ok((parse_key(key, vars)?, Felt::ZERO)))
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+    for write in &file.write {
+        let key = parse_key(&write.key, vars)?;
+        let value = parse_felt_or_short_string(
+            &format!("value of storage key {}", write.key),
+            &expand(&write.value, vars)?,
+        )?;
+        writes.push((key, value));
+    }
+
+    let expect = file.expect;
+    let parse_expected = |name: &str, value: Option<String>| {
+        value
+            .map(|value| parse_felt_or_short_string(name, &expand(&value, vars)?))
+            .transpose()
+    };
+    let expect = ExpectedToken {
+        total_supply: parse_expected("expected total_supply", expect.total_supply)?.map(U256::from),
+        owner: parse_expected("expected owner", expect.owner)?,
+        name: expect.name,
+        symbol: expect.symbol,
+        decimals: expect.decimals,
+    };
+
+    Ok(StorageMigration { writes, expect })
+}
+
+/// Checks that every migrated key holds its value and that the ERC20 getters return the
+/// expected values, all the mismatches are reported at once
+pub async fn verify_migration(
+    provider: &JsonRpcClient<FailoverTransport>,
+    contract: Felt,
+    migration: &StorageMigration,
+    block_id: BlockId,
+) -> Result<(), Box<dyn Error>> {
+    let mut mismatches = vec![];
+
+    // a key written several times ends up with its last value
+    let mut seen = HashSet::new();
+    for (key, value) in migration.writes.iter().rev() {
+        if !seen.insert(*key) {
+            continue;
+        }
+        let current = provider.get_storage_at(contract, *key, block_id).await?;
+        if current != *value {
+            mismatches.push(format!(
+                "storage key 0x{:x} is 0x{:x}, expected 0x{:x}",
+                key, current, value
+            ));
+        }
+    }
+
+    let expect = &migration.expect;
+    if let Some(expected) = &expect.name {
+        let name = call_string(provider, contract, selector!("name"), block_id).await?;
+        if name != *expected {
+            mismatches.push(format!("name() is {name:?}, expected {expected:?}"));
+        }
+    }
+    if let Some(expected) = &expect.symbol {
+        let symbol = call_string(provider, contract, selector!("symbol"), block_id).await?;
+        if symbol != *expected {
+            mismatches.push(format!("symbol() is {symbol:?}, expected {expected:?}"));
+        }
+    }
+    if let Some(expected) = expect.decimals {
+        let decimals: u8 =
+            call_view(provider, contract, selector!("decimals"), vec![], block_id).await?;
+        if decimals != expected {
+            mismatches.push(format!("decimals() is {decimals}, expected {expected}"));
+        }
+    }
+    if let Some(expected) = expect.total_supply {
+        let total_supply: U256 = call_view(
+            provider,
+            contract,
+            selector!("total_supply"),
+            vec![],
+            block_id,
+        )
+        .await?;
+        if total_supply != expected {
+            mismatches.push(format!(
+                "total_supply() is {total_supply}, expected {expected}"
+            ));
+        }
+    }
+    if let Some(expected) = expect.owner {
+        let owner: Felt =
+            call_view(provider, contract, selector!("owner"), vec![], block_id).await?;
+        if owner != expected {
+            mismatches.push(format!(
+                "owner() is 0x{:x}, expected 0x{:x}",
+                owner, expected
+            ));
+        }
+    }
+
+    if !mismatches.is_empty() {
+        return Err(format!(
+            "migration of 0x{:x} not applied as expected:\n  {}",
+            contract,
+            mismatches.join("\n  ")
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strk_migration_follows_the_bootstrap_account() {
+        let account = Felt::from_hex_unchecked(
+            "0x1903ec7c4ee6a8fa0a403663b6bc4dc599c57bbe01b55ac38f119067e936ed6",
+        );
+        let migration = load_migration(
+            Path::new("migrations/strk.toml"),
+            &[("BOOTSTRAP_ACCOUNT", account)],
+        )
+        .unwrap();
+
+        let owner_key = storage_address("Ownable_owner").unwrap();
+        let balance_key = Felt::from_hex_unchecked(
+            "0x7b22f6af0c07a11d1e063665f52a20f2271a002ffcd7e1866dfa700d534d39b",
+        );
+        assert!(migration.writes.contains(&(owner_key, account)));
+        assert!(migration
+            .writes
+            .contains(&(balance_key, Felt::from(10_000_000_000_000_000_000u128))));
+        assert_eq!(migration.expect.owner, Some(account));
+    }
+}
@@ -0,0 +1,120 @@
+use starknet::{
+    accounts::{Account, ConnectedAccount, SingleOwnerAccount},
+    core::types::{Call, Felt},
+    macros::selector,
+    providers::{jsonrpc::JsonRpcClient, Provider},
+    signers::LocalWallet,
+};
+use std::error::Error;
+
+use crate::{
+    account::execute,
+    config::TxSettings,
+    provider::FailoverTransport,
+    storage::storage_address,
+    utils::{call_view, wait_for_confirmation},
+    ws::WsClient,
+};
+
+async fn owner_of(
+    account: &SingleOwnerAccount<&JsonRpcClient<FailoverTransport>, &LocalWallet>,
+    contract: Felt,
+) -> Result<Felt, Box<dyn Error>> {
+    call_view(
+        account.provider(),
+        contract,
+        selector!("owner"),
+        vec![],
+        account.block_id(),
+    )
+    .await
+}
+
+/// Transfers the ownership of an OZ `Ownable` contract and checks it with `owner()`
+pub async fn transfer_ownership(
+    account: &SingleOwnerAccount<&JsonRpcClient<FailoverTransport>, &LocalWallet>,
+    ws: Option<&WsClient>,
+    contract: Felt,
+    new_owner: Felt,
+    tx: TxSettings,
+) -> Result<(), Box<dyn Error>> {
+    if owner_of(account, contract).await? == new_owner {
+        return Ok(());
+    }
+
+    let call = Call {
+        to: contract,
+        selector: selector!("transfer_ownership"),
+        calldata: vec![new_owner],
+    };
+    let tx_hash = execute(account, vec![call], tx).await?.transaction_hash;
+    wait_for_confirmation(account.provider(), ws, tx_hash).await?;
+
+    let owner = owner_of(account, contract).await?;
+    if owner != new_owner {
+        return Err(format!(
+            "owner of 0x{:x} is 0x{:x} after the transfer, expected 0x{:x}",
+            contract, owner, new_owner
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Nominates a new governor of a legacy StarkWare proxy and checks the nomination.
+///
+/// Governance can't be handed over in one step: the nominee has to call `accept_governance`,
+/// then remove the deployer with `remove_governor`.
+pub async fn nominate_proxy_governor(
+    account: &SingleOwnerAccount<&JsonRpcClient<FailoverTransport>, &LocalWallet>,
+    ws: Option<&WsClient>,
+    proxy: Felt,
+    nominee: Felt,
+    tx: TxSettings,
+) -> Result<(), Box<dyn Error>> {
+    let is_governor = call_view::<Felt>(
+        account.provider(),
+        proxy,
+        selector!("is_governor"),
+        vec![nominee],
+        account.block_id(),
+    )
+    .await?;
+    if is_governor == Felt::ONE {
+        return Ok(());
+    }
+
+    let call = Call {
+        to: proxy,
+        selector: selector!("nominate_new_governor"),
+        calldata: vec![nominee],
+    };
+    let tx_hash = execute(account, vec![call], tx).await?.transaction_hash;
+    wait_for_confirmation(account.provider(), ws, tx_hash).await?;
+
+    let candidate = account
+        .provider()
+        .get_storage_at(
+            proxy,
+            storage_address(&format!("candidates[0x{:x}]", nominee))?,
+            account.block_id(),
+        )
+        .await?;
+    if candidate != Felt::ONE {
+        return Err(format!(
+            "0x{:x} is not a governor candidate of 0x{:x} after the nomination",
+            nominee, proxy
+        )
+        .into());
+    }
+    println!(
+        "0x{:x} nominated as governor of 0x{:x}, it must call accept_governance \
+         then remove_governor(0x{:x})",
+        nominee,
+        proxy,
+        account.address()
+    );
+
+    Ok(())
+}
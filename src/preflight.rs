@@ -0,0 +1,142 @@
+use serde_json::{json, Value};
+use starknet::{
+    core::types::{StarknetError, SyncStatusType},
+    providers::{JsonRpcClient, Provider, ProviderError},
+};
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    compat::RpcSpec,
+    config::TxVersion,
+    provider::{http_client, Endpoint, FailoverTransport, FailoverTransportError},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Parses the spec version of the node and checks that transactions of `tx_version` can be
+/// sent over it
+pub fn check_spec(version: &str, tx_version: TxVersion) -> Result<RpcSpec, Box<dyn Error>> {
+    let spec = RpcSpec::parse(version)?;
+    if !spec.supports_writes(tx_version) {
+        return Err(FailoverTransportError::ReadOnlySpec {
+            spec,
+            version: tx_version,
+        }
+        .into());
+    }
+
+    Ok(spec)
+}
+
+/// Single readiness probe of the node, returns why it isn't ready yet
+async fn probe_node(provider: &JsonRpcClient<FailoverTransport>) -> Result<Option<u64>, String> {
+    provider
+        .chain_id()
+        .await
+        .map_err(|e| format!("chain id unavailable: {e}"))?;
+
+    if let SyncStatusType::Syncing(status) = provider
+        .syncing()
+        .await
+        .map_err(|e| format!("sync status unavailable: {e}"))?
+    {
+        return Err(format!(
+            "node is syncing ({}/{})",
+            status.current_block_num, status.highest_block_num
+        ));
+    }
+
+    // a fresh devnet has no block until the bootstrap transactions close block 0
+    match provider.block_number().await {
+        Ok(block_number) => Ok(Some(block_number)),
+        Err(ProviderError::StarknetError(StarknetError::NoBlocks)) => Ok(None),
+        Err(e) => Err(format!("block number unavailable: {e}")),
+    }
+}
+
+/// Checks that the admin endpoint answers JSON-RPC, an error object (e.g. unknown method) still counts
+async fn probe_admin(admin: &Endpoint) -> Result<(), String> {
+    let body = json!({
+        "jsonrpc": "2.0",
+        "method": "madara_ping",
+        "params": [],
+        "id": 0
+    });
+
+    let response = admin
+        .post(&http_client())
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| format!("admin endpoint unreachable: {e}"))?
+        .json::<Value>()
+        .await
+        .map_err(|e| format!("admin endpoint did not answer JSON-RPC: {e}"))?;
+
+    match response.get("jsonrpc") {
+        Some(_) => Ok(()),
+        None => Err(format!(
+            "admin endpoint did not answer JSON-RPC: {response}"
+        )),
+    }
+}
+
+/// Polls the node and its admin endpoint until both are ready or `timeout` is elapsed.
+/// A spec version that can't take `tx_version` transactions fails right away since waiting
+/// won't fix it.
+pub async fn wait_for_node(
+    provider: &JsonRpcClient<FailoverTransport>,
+    admin: &Endpoint,
+    tx_version: TxVersion,
+    timeout: Duration,
+) -> Result<(), Box<dyn Error>> {
+    // also bounds a probe that is still retrying when the timeout is reached
+    tokio::time::timeout(timeout, poll_node(provider, admin, tx_version, timeout))
+        .await
+        .map_err(|_| format!("node not ready after {:?}: request timed out", timeout))?
+}
+
+async fn poll_node(
+    provider: &JsonRpcClient<FailoverTransport>,
+    admin: &Endpoint,
+    tx_version: TxVersion,
+    timeout: Duration,
+) -> Result<(), Box<dyn Error>> {
+    let start = Instant::now();
+
+    loop {
+        let status = match provider.spec_version().await {
+            Ok(version) => {
+                check_spec(&version, tx_version)?;
+                match probe_node(provider).await {
+                    Ok(block_number) => probe_admin(admin).await.map(|_| (version, block_number)),
+                    Err(e) => Err(e),
+                }
+            }
+            Err(e) => Err(format!("spec version unavailable: {e}")),
+        };
+
+        match status {
+            Ok((version, block_number)) => {
+                match block_number {
+                    Some(block_number) => println!(
+                        "Node ready (RPC spec {}, block number {})",
+                        version, block_number
+                    ),
+                    None => println!("Node ready (RPC spec {}, no block yet)", version),
+                }
+                return Ok(());
+            }
+            Err(reason) if start.elapsed() >= timeout => {
+                return Err(format!("node not ready after {:?}: {reason}", timeout).into());
+            }
+            Err(reason) => {
+                println!("Waiting for node to be ready: {reason}");
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+}
@@ -0,0 +1,498 @@
+use async_trait::async_trait;
+use rand::Rng;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use starknet::providers::{
+    jsonrpc::{JsonRpcMethod, JsonRpcResponse, JsonRpcTransport},
+    ProviderRequestData, Url,
+};
+use std::{
+    fmt,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+use tokio::sync::OnceCell;
+
+use crate::{
+    compat::{transaction_version, RpcSpec},
+    config::TxVersion,
+};
+
+#[derive(Debug, Clone)]
+pub enum Auth {
+    Bearer(String),
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+}
+
+/// An HTTP endpoint along with the headers and credentials it expects
+#[derive(Debug, Clone)]
+pub struct Endpoint {
+    pub url: Url,
+    pub headers: Vec<(String, String)>,
+    pub auth: Option<Auth>,
+}
+
+impl From<Url> for Endpoint {
+    fn from(url: Url) -> Self {
+        Self {
+            url,
+            headers: vec![],
+            auth: None,
+        }
+    }
+}
+
+impl Endpoint {
+    /// Builds a POST request to the endpoint with its headers and credentials
+    pub fn post(&self, client: &Client) -> RequestBuilder {
+        let mut request = client.post(self.url.clone());
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+
+        match &self.auth {
+            Some(Auth::Bearer(token)) => request.bearer_auth(token),
+            Some(Auth::Basic { username, password }) => {
+                request.basic_auth(username, password.as_ref())
+            }
+            None => request,
+        }
+    }
+}
+
+/// Time allowed to connect to an endpoint
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Time allowed for a whole request, from sending it to reading the response
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// HTTP client for JSON-RPC endpoints, a stalled node fails the request instead of hanging
+pub fn http_client() -> Client {
+    Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .expect("failed to build the HTTP client")
+}
+
+/// How failed requests are retried
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt, across all endpoints
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled on each retry and randomized by up to 50%
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn delay(&self, retry: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay);
+        let jitter = rand::thread_rng().gen_range(0.0..=0.5);
+        delay.mul_f64(1.0 + jitter)
+    }
+}
+
+#[derive(Debug)]
+pub enum FailoverTransportError {
+    Http(reqwest::Error),
+    Status(StatusCode),
+    Json(serde_json::Error),
+    UnexpectedResponseId(u64),
+    UnsupportedSpec(String),
+    /// The node's spec version doesn't accept transactions of this version
+    ReadOnlySpec {
+        spec: RpcSpec,
+        version: TxVersion,
+    },
+    /// A write failed after it may have reached the node, it is not resent to avoid duplicates
+    AmbiguousWrite {
+        url: Url,
+        error: Box<FailoverTransportError>,
+    },
+}
+
+impl fmt::Display for FailoverTransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http(e) => write!(f, "{e}"),
+            Self::Status(status) => write!(f, "HTTP status {status}"),
+            Self::Json(e) => write!(f, "{e}"),
+            Self::UnexpectedResponseId(id) => write!(f, "unexpected response ID: {id}"),
+            Self::UnsupportedSpec(reason) => write!(f, "{reason}"),
+            Self::ReadOnlySpec { spec, version } => write!(
+                f,
+                "{} transactions can't be sent over RPC {spec}, point the RPC URL to the \
+                 node's 0.7 endpoint (e.g. /rpc/v0_7_1/ on Madara)",
+                match version {
+                    TxVersion::V1 => "v1",
+                    TxVersion::V3 => "v3",
+                }
+            ),
+            Self::AmbiguousWrite { url, error } => write!(
+                f,
+                "transaction submission to {url} failed after being sent ({error}), \
+                 it may have been accepted and was not resent"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FailoverTransportError {}
+
+impl FailoverTransportError {
+    /// Whether the request surely never reached the node, so that resending it can't duplicate it
+    fn is_undelivered(&self) -> bool {
+        match self {
+            Self::Http(e) => e.is_connect() || e.is_builder(),
+            // rate limiters reject before forwarding to the node
+            Self::Status(status) => *status == StatusCode::TOO_MANY_REQUESTS,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct JsonRpcRequest<T> {
+    id: u64,
+    jsonrpc: &'static str,
+    method: JsonRpcMethod,
+    params: T,
+}
+
+impl<T: Serialize> JsonRpcRequest<T> {
+    fn new(id: u64, method: JsonRpcMethod, params: T) -> Self {
+        Self {
+            id,
+            jsonrpc: "2.0",
+            method,
+            params,
+        }
+    }
+
+    fn to_string(&self) -> Result<String, FailoverTransportError> {
+        serde_json::to_string(self).map_err(FailoverTransportError::Json)
+    }
+}
+
+/// Mirrors the private mapping of starknet-providers, needed to build batch requests
+fn jsonrpc_method(request: &ProviderRequestData) -> JsonRpcMethod {
+    use ProviderRequestData as R;
+
+    match request {
+        R::SpecVersion(_) => JsonRpcMethod::SpecVersion,
+        R::GetBlockWithTxHashes(_) => JsonRpcMethod::GetBlockWithTxHashes,
+        R::GetBlockWithTxs(_) => JsonRpcMethod::GetBlockWithTxs,
+        R::GetBlockWithReceipts(_) => JsonRpcMethod::GetBlockWithReceipts,
+        R::GetStateUpdate(_) => JsonRpcMethod::GetStateUpdate,
+        R::GetStorageAt(_) => JsonRpcMethod::GetStorageAt,
+        R::GetTransactionStatus(_) => JsonRpcMethod::GetTransactionStatus,
+        R::GetTransactionByHash(_) => JsonRpcMethod::GetTransactionByHash,
+        R::GetTransactionByBlockIdAndIndex(_) => JsonRpcMethod::GetTransactionByBlockIdAndIndex,
+        R::GetTransactionReceipt(_) => JsonRpcMethod::GetTransactionReceipt,
+        R::GetClass(_) => JsonRpcMethod::GetClass,
+        R::GetClassHashAt(_) => JsonRpcMethod::GetClassHashAt,
+        R::GetClassAt(_) => JsonRpcMethod::GetClassAt,
+        R::GetBlockTransactionCount(_) => JsonRpcMethod::GetBlockTransactionCount,
+        R::Call(_) => JsonRpcMethod::Call,
+        R::EstimateFee(_) => JsonRpcMethod::EstimateFee,
+        R::EstimateMessageFee(_) => JsonRpcMethod::EstimateMessageFee,
+        R::BlockNumber(_) => JsonRpcMethod::BlockNumber,
+        R::BlockHashAndNumber(_) => JsonRpcMethod::BlockHashAndNumber,
+        R::ChainId(_) => JsonRpcMethod::ChainId,
+        R::Syncing(_) => JsonRpcMethod::Syncing,
+        R::GetEvents(_) => JsonRpcMethod::GetEvents,
+        R::GetNonce(_) => JsonRpcMethod::GetNonce,
+        R::AddInvokeTransaction(_) => JsonRpcMethod::AddInvokeTransaction,
+        R::AddDeclareTransaction(_) => JsonRpcMethod::AddDeclareTransaction,
+        R::AddDeployAccountTransaction(_) => JsonRpcMethod::AddDeployAccountTransaction,
+        R::TraceTransaction(_) => JsonRpcMethod::TraceTransaction,
+        R::SimulateTransactions(_) => JsonRpcMethod::SimulateTransactions,
+        R::TraceBlockTransactions(_) => JsonRpcMethod::TraceBlockTransactions,
+    }
+}
+
+/// Queries of the account that may fall back from the pending block to the latest one
+fn has_latest_fallback(method: JsonRpcMethod) -> bool {
+    matches!(
+        method,
+        JsonRpcMethod::GetNonce | JsonRpcMethod::EstimateFee | JsonRpcMethod::EstimateMessageFee
+    )
+}
+
+/// Whether the error of a response means the node can't serve the pending block: block not
+/// found or unknown tag. Internal errors are not retried on the latest block, they would hide
+/// node failures behind a stale nonce or fee.
+fn is_pending_unavailable(response: &Value) -> bool {
+    matches!(
+        response.pointer("/error/code").and_then(Value::as_i64),
+        Some(24 | -32602)
+    )
+}
+
+fn is_write(method: JsonRpcMethod) -> bool {
+    matches!(
+        method,
+        JsonRpcMethod::AddInvokeTransaction
+            | JsonRpcMethod::AddDeclareTransaction
+            | JsonRpcMethod::AddDeployAccountTransaction
+    )
+}
+
+/// A [`JsonRpcTransport`] over several HTTP endpoints of the same chain.
+///
+/// Reads are retried with exponential backoff and jitter, moving to the next endpoint on each
+/// failure. Transaction submissions are only retried when the request provably never reached
+/// the node, since resending a signed transaction that was accepted would fail on the nonce
+/// at best and hide the original hash at worst.
+#[derive(Debug)]
+pub struct FailoverTransport {
+    client: Client,
+    endpoints: Vec<Endpoint>,
+    policy: RetryPolicy,
+    /// Index of the endpoint that answered last, requests start there
+    current: AtomicUsize,
+    /// Detected on the first request
+    spec: OnceCell<RpcSpec>,
+    /// Set once a pending query failed, nonce and fee queries then use the latest block
+    pending_unavailable: AtomicBool,
+}
+
+impl FailoverTransport {
+    pub fn new(endpoints: Vec<Endpoint>, policy: RetryPolicy) -> Self {
+        assert!(
+            !endpoints.is_empty(),
+            "at least one RPC endpoint is required"
+        );
+
+        Self {
+            client: http_client(),
+            endpoints,
+            policy,
+            current: AtomicUsize::new(0),
+            spec: OnceCell::new(),
+            pending_unavailable: AtomicBool::new(false),
+        }
+    }
+
+    /// Spec version of the node, detected once and shared by all endpoints
+    async fn spec(&self) -> Result<RpcSpec, FailoverTransportError> {
+        self.spec
+            .get_or_try_init(|| async {
+                let body = JsonRpcRequest::new(0, JsonRpcMethod::SpecVersion, ()).to_string()?;
+                match self.send(body, false).await? {
+                    JsonRpcResponse::Success { result, .. } => {
+                        RpcSpec::parse(&result).map_err(FailoverTransportError::UnsupportedSpec)
+                    }
+                    JsonRpcResponse::<String>::Error { error, .. } => {
+                        Err(FailoverTransportError::UnsupportedSpec(format!(
+                            "spec version unavailable: {}",
+                            error.message
+                        )))
+                    }
+                }
+            })
+            .await
+            .copied()
+    }
+
+    fn adapted_request<P: Serialize>(
+        &self,
+        spec: RpcSpec,
+        id: u64,
+        method: JsonRpcMethod,
+        params: P,
+    ) -> Result<JsonRpcRequest<Value>, FailoverTransportError> {
+        let mut params = serde_json::to_value(params).map_err(FailoverTransportError::Json)?;
+        if is_write(method) {
+            let version = transaction_version(&params);
+            if !spec.supports_writes(version) {
+                return Err(FailoverTransportError::ReadOnlySpec { spec, version });
+            }
+        }
+        spec.adapt_request(&mut params);
+
+        Ok(JsonRpcRequest::new(id, method, params))
+    }
+
+    async fn post(
+        &self,
+        endpoint: &Endpoint,
+        body: &str,
+    ) -> Result<String, FailoverTransportError> {
+        let response = endpoint
+            .post(&self.client)
+            .body(body.to_owned())
+            .header("Content-Type", "application/json")
+            .send()
+            .await
+            .map_err(FailoverTransportError::Http)?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(FailoverTransportError::Status(status));
+        }
+
+        response.text().await.map_err(FailoverTransportError::Http)
+    }
+
+    /// Sends `body` and parses the response, failing over between endpoints according to the policy
+    async fn send<T: DeserializeOwned>(
+        &self,
+        body: String,
+        write: bool,
+    ) -> Result<T, FailoverTransportError> {
+        let mut retry = 0;
+
+        loop {
+            let index = self.current.load(Ordering::Relaxed) % self.endpoints.len();
+            let endpoint = &self.endpoints[index];
+
+            let error = match self.post(endpoint, &body).await {
+                Ok(response) => match serde_json::from_str(&response) {
+                    Ok(parsed) => {
+                        self.current.store(index, Ordering::Relaxed);
+                        return Ok(parsed);
+                    }
+                    Err(e) => FailoverTransportError::Json(e),
+                },
+                Err(e) => e,
+            };
+
+            if write && !error.is_undelivered() {
+                return Err(FailoverTransportError::AmbiguousWrite {
+                    url: endpoint.url.clone(),
+                    error: Box::new(error),
+                });
+            }
+            if retry >= self.policy.max_retries {
+                return Err(error);
+            }
+
+            let next = (index + 1) % self.endpoints.len();
+            self.current.store(next, Ordering::Relaxed);
+            println!(
+                "RPC request to {} failed ({}), retrying on {}",
+                endpoint.url, error, self.endpoints[next].url
+            );
+            tokio::time::sleep(self.policy.delay(retry)).await;
+            retry += 1;
+        }
+    }
+}
+
+#[async_trait]
+impl JsonRpcTransport for FailoverTransport {
+    type Error = FailoverTransportError;
+
+    async fn send_request<P, R>(
+        &self,
+        method: JsonRpcMethod,
+        params: P,
+    ) -> Result<JsonRpcResponse<R>, Self::Error>
+    where
+        P: Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        // needed to detect the spec, and the same in every version
+        if matches!(method, JsonRpcMethod::SpecVersion) {
+            let body = JsonRpcRequest::new(0, method, params).to_string()?;
+            return self.send(body, false).await;
+        }
+
+        let write = is_write(method);
+        let spec = self.spec().await?;
+        let mut params = serde_json::to_value(params).map_err(FailoverTransportError::Json)?;
+        let fallback = has_latest_fallback(method) && params["block_id"] == "pending";
+        if fallback && self.pending_unavailable.load(Ordering::Relaxed) {
+            params["block_id"] = json!("latest");
+        }
+
+        let body = self
+            .adapted_request(spec, 0, method, &params)?
+            .to_string()?;
+        let mut response: Value = self.send(body, write).await?;
+
+        if fallback && params["block_id"] == "pending" && is_pending_unavailable(&response) {
+            println!(
+                "Pending block unavailable ({}), falling back to latest for nonce and fee queries",
+                response["error"]["message"]
+            );
+            self.pending_unavailable.store(true, Ordering::Relaxed);
+            params["block_id"] = json!("latest");
+            let body = self
+                .adapted_request(spec, 0, method, &params)?
+                .to_string()?;
+            response = self.send(body, write).await?;
+        }
+
+        if let Some(result) = response.get_mut("result") {
+            spec.adapt_response(method, result);
+        }
+
+        serde_json::from_value(response).map_err(FailoverTransportError::Json)
+    }
+
+    async fn send_requests<R>(
+        &self,
+        requests: R,
+    ) -> Result<Vec<JsonRpcResponse<Value>>, Self::Error>
+    where
+        R: AsRef<[ProviderRequestData]> + Send + Sync,
+    {
+        let requests = requests.as_ref();
+        let methods = requests.iter().map(jsonrpc_method).collect::<Vec<_>>();
+        let write = methods.iter().any(|method| is_write(*method));
+        let spec = self.spec().await?;
+
+        let body = serde_json::to_string(
+            &requests
+                .iter()
+                .zip(&methods)
+                .enumerate()
+                .map(|(id, (request, method))| {
+                    self.adapted_request(spec, id as u64, *method, request)
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        )
+        .map_err(FailoverTransportError::Json)?;
+
+        let mut parsed: Vec<Value> = self.send(body, write).await?;
+
+        // servers don't necessarily keep the order of the batch
+        let mut responses = vec![None; requests.len()];
+        for response in parsed.iter_mut() {
+            let id = response
+                .get("id")
+                .and_then(Value::as_u64)
+                .unwrap_or(u64::MAX);
+            let method = *methods
+                .get(id as usize)
+                .ok_or(FailoverTransportError::UnexpectedResponseId(id))?;
+            if let Some(result) = response.get_mut("result") {
+                spec.adapt_response(method, result);
+            }
+            responses[id as usize] = Some(
+                serde_json::from_value(response.take()).map_err(FailoverTransportError::Json)?,
+            );
+        }
+
+        Ok(responses.into_iter().flatten().collect())
+    }
+}
@@ -0,0 +1,230 @@
+use starknet::{
+    core::{
+        crypto::pedersen_hash,
+        types::Felt,
+        utils::{cairo_short_string_to_felt, normalize_address, starknet_keccak},
+    },
+    providers::{jsonrpc::JsonRpcClient, Provider},
+};
+use starknet_crypto::poseidon_permute_comp;
+use std::error::Error;
+
+use crate::{
+    cli::{ConfigArgs, StorageArgs},
+    config::{load_l3_config, parse_felt_or_short_string},
+    provider::FailoverTransport,
+};
+
+/// Bytes per word of a ByteArray
+const BYTES_PER_WORD: usize = 31;
+/// Words per chunk of a ByteArray, each chunk has its own base address
+const WORDS_PER_CHUNK: usize = 256;
+
+fn parse_name(path: &str, name: &str) -> Result<Felt, Box<dyn Error>> {
+    if name.is_empty() || !name.is_ascii() {
+        return Err(format!("invalid storage path `{path}`: bad variable name `{name}`").into());
+    }
+    Ok(starknet_keccak(name.as_bytes()))
+}
+
+/// Parses a map key: a hex felt, a decimal number or a short string
+fn parse_key(path: &str, key: &str) -> Result<Felt, Box<dyn Error>> {
+    if !key.is_empty() && key.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Felt::from_dec_str(key)
+            .map_err(|e| format!("invalid storage path `{path}`: bad key `{key}`: {e}").into());
+    }
+    parse_felt_or_short_string(path, key)
+}
+
+/// Computes the storage address of a path like `ERC20_balances[0x123]`.
+///
+/// A path starts with a variable name, hashed with `sn_keccak`. Each `[key]` is a `Map` or
+/// `LegacyMap` entry, Pedersen-chained with the key (comma separated for keys serialized to
+/// several felts, such as a u256 `[low, high]`). A key is a hex felt, a decimal number or a
+/// short string. Each `.member` is a member of a storage node
+/// or of a non-flattened substorage, Pedersen-chained with its `sn_keccak`. Components
+/// embedded with `#[substorage(v0)]` are flattened and use their variable name directly,
+/// e.g. `ERC20_name`.
+pub fn storage_address(path: &str) -> Result<Felt, Box<dyn Error>> {
+    let end = path.find(['.', '[']).unwrap_or(path.len());
+    let mut address = parse_name(path, &path[..end])?;
+    let mut rest = &path[end..];
+
+    while !rest.is_empty() {
+        if let Some(member) = rest.strip_prefix('.') {
+            let end = member.find(['.', '[']).unwrap_or(member.len());
+            address = pedersen_hash(&address, &parse_name(path, &member[..end])?);
+            rest = &member[end..];
+        } else if let Some(keys) = rest.strip_prefix('[') {
+            let end = keys
+                .find(']')
+                .ok_or_else(|| format!("invalid storage path `{path}`: unclosed `[`"))?;
+            for key in keys[..end].split(',') {
+                address = pedersen_hash(&address, &parse_key(path, key.trim())?);
+            }
+            rest = &keys[end + 1..];
+        } else {
+            return Err(format!("invalid storage path `{path}`: unexpected `{rest}`").into());
+        }
+    }
+
+    Ok(normalize_address(address))
+}
+
+/// Address of the word `index` of the ByteArray stored at `base`
+fn byte_array_word_address(base: Felt, index: usize) -> Felt {
+    let mut state = [
+        base,
+        Felt::from(index / WORDS_PER_CHUNK),
+        cairo_short_string_to_felt("ByteArray").unwrap(),
+    ];
+    poseidon_permute_comp(&mut state);
+    normalize_address(state[0]) + Felt::from(index % WORDS_PER_CHUNK)
+}
+
+/// Storage writes of `value` stored as a ByteArray at `base`: the byte length at `base`,
+/// then the full 31-byte words and the pending word
+pub fn byte_array_storage(base: Felt, value: &str) -> Vec<(Felt, Felt)> {
+    let mut writes = vec![(base, Felt::from(value.len()))];
+    writes.extend(
+        value
+            .as_bytes()
+            .chunks(BYTES_PER_WORD)
+            .enumerate()
+            .map(|(index, word)| {
+                (
+                    byte_array_word_address(base, index),
+                    Felt::from_bytes_be_slice(word),
+                )
+            }),
+    );
+    // an empty pending word is still read, writing it clears a previous value
+    if value.len().is_multiple_of(BYTES_PER_WORD) {
+        writes.push((
+            byte_array_word_address(base, value.len() / BYTES_PER_WORD),
+            Felt::ZERO,
+        ));
+    }
+
+    writes
+}
+
+/// Prints the storage address of a path, or the writes of a ByteArray, as migration file
+/// entries along with the current values when a contract is given
+pub async fn print_storage(args: &StorageArgs, config: &ConfigArgs) -> Result<(), Box<dyn Error>> {
+    let address = storage_address(&args.path)?;
+    let writes = match &args.byte_array {
+        Some(value) => byte_array_storage(address, value),
+        None => vec![(address, Felt::ZERO)],
+    };
+
+    let reader = match args.contract {
+        Some(contract) => {
+            let config = load_l3_config(config)?;
+            let provider = JsonRpcClient::new(FailoverTransport::new(
+                config.l3.rpc_endpoints,
+                config.l3.retry,
+            ));
+            Some((provider, contract, config.l3.block_id))
+        }
+        None => None,
+    };
+
+    println!("# {}", args.path);
+    for (key, value) in writes {
+        if args.byte_array.is_some() {
+            println!(
+                "[[write]]\nkey = \"0x{:x}\"\nvalue = \"0x{:x}\"",
+                key, value
+            );
+        } else {
+            println!("key = \"0x{:x}\"", key);
+        }
+        if let Some((provider, contract, block_id)) = &reader {
+            let current = provider.get_storage_at(contract, key, block_id).await?;
+            println!("# current value: 0x{:x}", current);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn storage_address_of_variables_and_map_entries() {
+        assert_eq!(
+            storage_address("ERC20_total_supply").unwrap(),
+            Felt::from_hex_unchecked(
+                "0x110e2f729c9c2b988559994a3daccd838cf52faf88e18101373e67dd061455a"
+            )
+        );
+        assert_eq!(
+            storage_address("ERC20_name").unwrap(),
+            Felt::from_hex_unchecked(
+                "0x341c1bdfd89f69748aa00b5742b03adbffd79b8e80cab5c50d91cd8c2a79be1"
+            )
+        );
+        assert_eq!(
+            storage_address("ERC20_balances[0x10]").unwrap(),
+            Felt::from_hex_unchecked(
+                "0x11cb0dc747a73020cbd50eac7460edfaa7d67b0e05823b882b05c3f33b1c73e"
+            )
+        );
+        assert_eq!(
+            storage_address("ERC20_allowances[0x1, 0x2]").unwrap(),
+            Felt::from_hex_unchecked(
+                "0x53b9f6a59ff5232c4a46837ed4161c12e653509291eb856a3433c1754cc3697"
+            )
+        );
+    }
+
+    #[test]
+    fn decimal_keys_are_numbers() {
+        assert_eq!(
+            storage_address("ERC20_balances[16]").unwrap(),
+            storage_address("ERC20_balances[0x10]").unwrap()
+        );
+        assert_ne!(
+            storage_address("ERC20_balances[16]").unwrap(),
+            storage_address("ERC20_balances[0x3136]").unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_paths_are_rejected() {
+        assert!(storage_address("").is_err());
+        assert!(storage_address("ERC20_balances[0x10").is_err());
+        assert!(storage_address("ERC20_balances[0x10]x").is_err());
+    }
+
+    #[test]
+    fn byte_array_storage_writes_words_and_pending_word() {
+        let base = storage_address("ERC20_name").unwrap();
+        assert_eq!(
+            byte_array_storage(base, "Starknet Token"),
+            vec![
+                (base, Felt::from(14)),
+                (
+                    Felt::from_hex_unchecked(
+                        "0x35b0c37f7f34be47076c6cfbcf811ad0769dbc81f4c509ee9613e0b0c648ca9"
+                    ),
+                    Felt::from_bytes_be_slice(b"Starknet Token"),
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn byte_array_storage_clears_empty_pending_word() {
+        let base = storage_address("ERC20_name").unwrap();
+        let value = "a".repeat(BYTES_PER_WORD);
+        let writes = byte_array_storage(base, &value);
+        assert_eq!(writes.len(), 3);
+        assert_eq!(writes[0], (base, Felt::from(BYTES_PER_WORD)));
+        assert_eq!(writes[1].1, Felt::from_bytes_be_slice(value.as_bytes()));
+        assert_eq!(writes[2], (writes[1].0 + Felt::ONE, Felt::ZERO));
+    }
+}
@@ -1,18 +1,16 @@
 use starknet::{
-    accounts::{Account, SingleOwnerAccount},
-    contract::ContractFactory,
     core::{
         codec::Encode,
-        types::{ByteArray, Call, Felt, U256},
-        utils::get_udc_deployed_address,
+        types::{BlockId, ByteArray, Call, Felt, U256},
     },
     macros::selector,
-    providers::jsonrpc::{HttpTransport, JsonRpcClient},
-    signers::LocalWallet,
+    providers::jsonrpc::JsonRpcClient,
 };
 
 use std::error::Error;
 
+use crate::{provider::FailoverTransport, utils::call_view};
+
 pub fn constructor_call_data(name: &str, symbol: &str, owner: Felt) -> Vec<Felt> {
     let mut constructor_calldata = vec![];
     ByteArray::from(name)
@@ -26,76 +24,49 @@ pub fn constructor_call_data(name: &str, symbol: &str, owner: Felt) -> Vec<Felt>
     constructor_calldata
 }
 
-pub async fn deploy_token(
-    account: &SingleOwnerAccount<&JsonRpcClient<HttpTransport>, &LocalWallet>,
-    class_hash: Felt,
-    name: &str,
-    symbol: &str,
-    owner: Felt,
-    salt: Felt,
-) -> Result<(Felt, Felt), Box<dyn Error>> {
-    let constructor_calldata = constructor_call_data(name, symbol, owner);
-
-    let contract_address = get_udc_deployed_address(
-        salt,
-        class_hash,
-        &starknet::core::utils::UdcUniqueness::NotUnique,
-        &constructor_calldata,
-    );
-
-    let contract_factory = ContractFactory::new(class_hash, account);
-
-    let deployment = contract_factory
-        .deploy_v1(constructor_calldata, salt, false)
-        .max_fee(Felt::ZERO)
-        .send()
-        .await?;
-
-    Ok((deployment.transaction_hash, contract_address))
-}
-
-pub async fn mint(
-    account: &SingleOwnerAccount<&JsonRpcClient<HttpTransport>, &LocalWallet>,
-    token_address: &Felt,
-    recipient: &Felt,
-    amount: &U256,
-) -> Result<Felt, Box<dyn Error>> {
-    let calldata = vec![*recipient, amount.low().into(), amount.high().into()];
-
-    let call = Call {
-        to: *token_address,
+pub fn mint_call(token_address: Felt, recipient: Felt, amount: U256) -> Call {
+    Call {
+        to: token_address,
         selector: selector!("mint"),
-        calldata,
-    };
-
-    let mint_tx = account
-        .execute_v1(vec![call])
-        .max_fee(Felt::ZERO)
-        .send()
-        .await?;
-
-    Ok(mint_tx.transaction_hash)
+        calldata: vec![recipient, amount.low().into(), amount.high().into()],
+    }
 }
 
-pub async fn transfer(
-    account: &SingleOwnerAccount<&JsonRpcClient<HttpTransport>, &LocalWallet>,
-    token_address: &Felt,
-    recipient: &Felt,
-    amount: &U256,
-) -> Result<Felt, Box<dyn Error>> {
-    let calldata = vec![*recipient, amount.low().into(), amount.high().into()];
-
-    let call = Call {
-        to: *token_address,
+pub fn transfer_call(token_address: Felt, recipient: Felt, amount: U256) -> Call {
+    Call {
+        to: token_address,
         selector: selector!("transfer"),
-        calldata,
-    };
+        calldata: vec![recipient, amount.low().into(), amount.high().into()],
+    }
+}
 
-    let transfer_tx = account
-        .execute_v1(vec![call])
-        .max_fee(Felt::ZERO)
-        .send()
-        .await?;
+pub async fn balance_of(
+    provider: &JsonRpcClient<FailoverTransport>,
+    token_address: Felt,
+    owner: Felt,
+    block_id: BlockId,
+) -> Result<U256, Box<dyn Error>> {
+    call_view(
+        provider,
+        token_address,
+        selector!("balance_of"),
+        vec![owner],
+        block_id,
+    )
+    .await
+}
 
-    Ok(transfer_tx.transaction_hash)
+pub async fn decimals(
+    provider: &JsonRpcClient<FailoverTransport>,
+    token_address: Felt,
+    block_id: BlockId,
+) -> Result<u8, Box<dyn Error>> {
+    call_view(
+        provider,
+        token_address,
+        selector!("decimals"),
+        vec![],
+        block_id,
+    )
+    .await
 }
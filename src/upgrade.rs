@@ -0,0 +1,283 @@
+use starknet::{
+    accounts::{ConnectedAccount, ExecutionEncoding, SingleOwnerAccount},
+    core::{
+        types::{Call, Felt, MaybePendingBlockWithTxHashes},
+        utils::get_selector_from_name,
+    },
+    macros::selector,
+    providers::{jsonrpc::JsonRpcClient, Provider},
+    signers::{LocalWallet, SigningKey},
+};
+use std::{error::Error, time::Duration};
+
+use crate::{
+    account::{declare_v2_if_missing, execute},
+    cli::{ConfigArgs, UpgradeArgs},
+    config::{load_l3_config, parse_felt_or_short_string, TxSettings},
+    provider::FailoverTransport,
+    utils::{call_view, check_chain_id, confirm_declaration, wait_for_confirmation},
+    ws::{self, WsClient},
+};
+
+const DELAY_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// An upgrade of a legacy StarkWare proxy (`add_implementation`/`upgrade_to`)
+pub struct ProxyUpgrade {
+    pub implementation: Felt,
+    /// External initializer class run on upgrade, without one the init vector is passed to the
+    /// `initialize` of the implementation
+    pub eic: Option<Felt>,
+    pub init_vector: Vec<Felt>,
+    /// A final implementation can't be upgraded anymore
+    pub is_final: bool,
+}
+
+impl ProxyUpgrade {
+    /// Arguments shared by `add_implementation`, `implementation_time` and `upgrade_to`
+    fn calldata(&self) -> Vec<Felt> {
+        let mut calldata = vec![
+            self.implementation,
+            self.eic.unwrap_or(Felt::ZERO),
+            Felt::from(self.init_vector.len()),
+        ];
+        calldata.extend_from_slice(&self.init_vector);
+        calldata.push(Felt::from(self.is_final));
+        calldata
+    }
+}
+
+async fn call_proxy(
+    account: &SingleOwnerAccount<&JsonRpcClient<FailoverTransport>, &LocalWallet>,
+    proxy: Felt,
+    entry_point_selector: Felt,
+    calldata: Vec<Felt>,
+) -> Result<Felt, Box<dyn Error>> {
+    call_view(
+        account.provider(),
+        proxy,
+        entry_point_selector,
+        calldata,
+        account.block_id(),
+    )
+    .await
+}
+
+async fn block_timestamp(
+    account: &SingleOwnerAccount<&JsonRpcClient<FailoverTransport>, &LocalWallet>,
+) -> Result<u64, Box<dyn Error>> {
+    Ok(
+        match account
+            .provider()
+            .get_block_with_tx_hashes(account.block_id())
+            .await?
+        {
+            MaybePendingBlockWithTxHashes::Block(block) => block.timestamp,
+            MaybePendingBlockWithTxHashes::PendingBlock(block) => block.timestamp,
+        },
+    )
+}
+
+/// Waits until the block timestamp reaches the time at which the upgrade is allowed
+async fn wait_for_upgrade_time(
+    account: &SingleOwnerAccount<&JsonRpcClient<FailoverTransport>, &LocalWallet>,
+    proxy: Felt,
+    upgrade: &ProxyUpgrade,
+) -> Result<(), Box<dyn Error>> {
+    let delay = call_proxy(account, proxy, selector!("get_upgrade_delay"), vec![]).await?;
+    if delay == Felt::ZERO {
+        return Ok(());
+    }
+
+    let time: u64 = call_proxy(
+        account,
+        proxy,
+        selector!("implementation_time"),
+        upgrade.calldata(),
+    )
+    .await?
+    .try_into()?;
+    println!(
+        "Upgrade delay of proxy 0x{:x} is {} seconds, upgrade allowed at timestamp {}",
+        proxy, delay, time
+    );
+
+    loop {
+        let now = block_timestamp(account).await?;
+        if now >= time {
+            return Ok(());
+        }
+        println!("Waiting {} seconds for the upgrade delay", time - now);
+        tokio::time::sleep(DELAY_POLL_INTERVAL.min(Duration::from_secs(time - now))).await;
+    }
+}
+
+/// Checks that the proxy runs the new implementation, either as its class after a
+/// `replace_class` or as the implementation it delegates to
+async fn check_implementation(
+    account: &SingleOwnerAccount<&JsonRpcClient<FailoverTransport>, &LocalWallet>,
+    proxy: Felt,
+    expected: Felt,
+) -> Result<(), Box<dyn Error>> {
+    let class_hash = account
+        .provider()
+        .get_class_hash_at(account.block_id(), proxy)
+        .await?;
+    if class_hash == expected {
+        return Ok(());
+    }
+
+    let implementation = call_proxy(account, proxy, selector!("implementation"), vec![]).await?;
+    if implementation != expected {
+        return Err(format!(
+            "proxy 0x{:x} implementation is 0x{:x} after the upgrade, expected 0x{:x}",
+            proxy, implementation, expected
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Registers the implementation on a legacy proxy, waits out the upgrade delay, upgrades
+/// and checks that the new implementation is active
+pub async fn upgrade_proxy(
+    account: &SingleOwnerAccount<&JsonRpcClient<FailoverTransport>, &LocalWallet>,
+    ws: Option<&WsClient>,
+    proxy: Felt,
+    upgrade: &ProxyUpgrade,
+    tx: TxSettings,
+) -> Result<(), Box<dyn Error>> {
+    let call = Call {
+        to: proxy,
+        selector: selector!("add_implementation"),
+        calldata: upgrade.calldata(),
+    };
+    let tx_hash = execute(account, vec![call], tx).await?.transaction_hash;
+    wait_for_confirmation(account.provider(), ws, tx_hash).await?;
+
+    wait_for_upgrade_time(account, proxy, upgrade).await?;
+
+    let call = Call {
+        to: proxy,
+        selector: selector!("upgrade_to"),
+        calldata: upgrade.calldata(),
+    };
+    let tx_hash = execute(account, vec![call], tx).await?.transaction_hash;
+    wait_for_confirmation(account.provider(), ws, tx_hash).await?;
+
+    check_implementation(account, proxy, upgrade.implementation).await
+}
+
+/// Upgrades an OpenZeppelin upgradeable contract with `upgrade`, or with `upgrade_and_call`
+/// when a `(selector, calldata)` call is given, then checks its class hash
+pub async fn upgrade_contract(
+    account: &SingleOwnerAccount<&JsonRpcClient<FailoverTransport>, &LocalWallet>,
+    ws: Option<&WsClient>,
+    contract: Felt,
+    class_hash: Felt,
+    call: Option<(Felt, Vec<Felt>)>,
+    tx: TxSettings,
+) -> Result<(), Box<dyn Error>> {
+    let call = match call {
+        Some((function, calldata)) => {
+            let mut upgrade_calldata = vec![class_hash, function, Felt::from(calldata.len())];
+            upgrade_calldata.extend(calldata);
+            Call {
+                to: contract,
+                selector: selector!("upgrade_and_call"),
+                calldata: upgrade_calldata,
+            }
+        }
+        None => Call {
+            to: contract,
+            selector: selector!("upgrade"),
+            calldata: vec![class_hash],
+        },
+    };
+    let tx_hash = execute(account, vec![call], tx).await?.transaction_hash;
+    wait_for_confirmation(account.provider(), ws, tx_hash).await?;
+
+    let current = account
+        .provider()
+        .get_class_hash_at(account.block_id(), contract)
+        .await?;
+    if current != class_hash {
+        return Err(format!(
+            "class hash of 0x{:x} is 0x{:x} after the upgrade, expected 0x{:x}",
+            contract, current, class_hash
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Runs the `upgrade` command from the configured account of the L3
+pub async fn upgrade_command(
+    args: &UpgradeArgs,
+    config: &ConfigArgs,
+) -> Result<(), Box<dyn Error>> {
+    let config = load_l3_config(config)?;
+    let account_address = config
+        .l3
+        .account_address
+        .ok_or("ACCOUNT_ADDRESS must be set to upgrade a contract")?;
+    let provider = JsonRpcClient::new(FailoverTransport::new(
+        config.l3.rpc_endpoints.clone(),
+        config.l3.retry,
+    ));
+    let chain_id = check_chain_id(&provider, config.l3.chain_id, config.allow_mainnet).await?;
+    let signer = LocalWallet::from(SigningKey::from_secret_scalar(config.l3.secret_key));
+    let mut account = SingleOwnerAccount::new(
+        &provider,
+        &signer,
+        account_address,
+        chain_id,
+        ExecutionEncoding::New,
+    );
+    account.set_block_id(config.l3.block_id);
+    let ws = ws::connect_or_poll(config.l3.ws.as_ref()).await;
+
+    let class_hash = match (&args.class, &args.compiled_class, args.class_hash) {
+        (Some(class), Some(compiled_class), _) => {
+            confirm_declaration(
+                &provider,
+                ws.as_ref(),
+                "implementation",
+                declare_v2_if_missing(&account, class, compiled_class, config.l3.tx).await?,
+            )
+            .await?
+        }
+        (_, _, Some(class_hash)) => class_hash,
+        _ => return Err("--class and --compiled-class, or --class-hash must be given".into()),
+    };
+
+    let call = match &args.call {
+        Some(name) => Some((
+            get_selector_from_name(name)?,
+            args.calldata
+                .iter()
+                .map(|value| parse_felt_or_short_string("calldata", value))
+                .collect::<Result<Vec<_>, _>>()?,
+        )),
+        None => None,
+    };
+
+    // accounts only accept upgrades from themselves, which is the default target
+    let contract = args.contract.unwrap_or(account_address);
+    upgrade_contract(
+        &account,
+        ws.as_ref(),
+        contract,
+        class_hash,
+        call,
+        config.l3.tx,
+    )
+    .await?;
+    println!(
+        "Contract 0x{:x} upgraded to class hash 0x{:x}",
+        contract, class_hash
+    );
+
+    Ok(())
+}
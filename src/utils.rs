@@ -1,29 +1,263 @@
+use serde_json::Value;
 use starknet::{
-    core::types::{Felt, TransactionExecutionStatus, TransactionStatus},
-    providers::{jsonrpc::HttpTransport, JsonRpcClient, Provider},
+    core::{
+        chain_id,
+        codec::Decode,
+        types::{
+            BlockId, BlockWithTxHashes, ByteArray, Felt, FunctionCall,
+            MaybePendingBlockWithTxHashes, TransactionExecutionStatus, TransactionStatus,
+        },
+        utils::parse_cairo_short_string,
+    },
+    providers::{JsonRpcClient, Provider},
 };
+use std::{error::Error, time::Duration};
 
+use crate::{provider::FailoverTransport, ws::WsClient};
+
+/// How long to wait for a notification before checking with a regular request
+const SUBSCRIPTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Displays a chain id as its short string when it is one, as hex otherwise
+pub fn format_chain_id(chain_id: Felt) -> String {
+    match parse_cairo_short_string(&chain_id) {
+        Ok(name) if !name.is_empty() && name.chars().all(|c| c.is_ascii_graphic()) => name,
+        _ => format!("0x{:x}", chain_id),
+    }
+}
+
+/// Calls a view function and decodes its result
+pub async fn call_view<T: for<'a> Decode<'a>>(
+    provider: &JsonRpcClient<FailoverTransport>,
+    contract: Felt,
+    entry_point_selector: Felt,
+    calldata: Vec<Felt>,
+    block_id: BlockId,
+) -> Result<T, Box<dyn Error>> {
+    let result = provider
+        .call(
+            FunctionCall {
+                contract_address: contract,
+                entry_point_selector,
+                calldata,
+            },
+            block_id,
+        )
+        .await?;
+
+    Ok(T::decode(&result)?)
+}
+
+/// Calls a view function returning a string, either a ByteArray or a Cairo 0 short string
+pub async fn call_string(
+    provider: &JsonRpcClient<FailoverTransport>,
+    contract: Felt,
+    entry_point_selector: Felt,
+    block_id: BlockId,
+) -> Result<String, Box<dyn Error>> {
+    let result = provider
+        .call(
+            FunctionCall {
+                contract_address: contract,
+                entry_point_selector,
+                calldata: vec![],
+            },
+            block_id,
+        )
+        .await?;
+
+    match result.as_slice() {
+        [short_string] => Ok(parse_cairo_short_string(short_string)?),
+        result => Ok(String::try_from(ByteArray::decode(result)?)?),
+    }
+}
+
+/// Fetches the chain id of the node and aborts if it is not the expected one,
+/// or if it is Starknet mainnet and `allow_mainnet` is not set.
+pub async fn check_chain_id(
+    provider: &JsonRpcClient<FailoverTransport>,
+    expected: Felt,
+    allow_mainnet: bool,
+) -> Result<Felt, Box<dyn std::error::Error>> {
+    let chain_id = provider.chain_id().await?;
+
+    if chain_id != expected {
+        return Err(format!(
+            "chain id mismatch: expected {}, node reports {}",
+            format_chain_id(expected),
+            format_chain_id(chain_id)
+        )
+        .into());
+    }
+    if chain_id == chain_id::MAINNET && !allow_mainnet {
+        return Err(
+            "refusing to send transactions to Starknet mainnet without --allow-mainnet".into(),
+        );
+    }
+
+    Ok(chain_id)
+}
+
+/// Maps a transaction status to the outcome of the wait, `None` while it isn't final
+fn final_status(
+    tx_hash: Felt,
+    status: TransactionStatus,
+) -> Option<Result<(), Box<dyn std::error::Error>>> {
+    match status {
+        TransactionStatus::AcceptedOnL2(TransactionExecutionStatus::Succeeded)
+        | TransactionStatus::AcceptedOnL1(TransactionExecutionStatus::Succeeded) => Some(Ok(())),
+        TransactionStatus::AcceptedOnL2(TransactionExecutionStatus::Reverted)
+        | TransactionStatus::AcceptedOnL1(TransactionExecutionStatus::Reverted) => {
+            Some(Err(format!("Transaction 0x{:x} reverted", tx_hash).into()))
+        }
+        TransactionStatus::Rejected => {
+            Some(Err(format!("Transaction 0x{:x} rejected", tx_hash).into()))
+        }
+        TransactionStatus::Received => None,
+    }
+}
+
+/// Parses the `status` of a `starknet_subscriptionTransactionStatus` notification
+fn parse_status_notification(notification: &Value) -> Option<TransactionStatus> {
+    let status = notification.get("status")?;
+    let execution = match status.get("execution_status").and_then(Value::as_str) {
+        Some("REVERTED") => TransactionExecutionStatus::Reverted,
+        _ => TransactionExecutionStatus::Succeeded,
+    };
+
+    match status.get("finality_status")?.as_str()? {
+        // 0.9 statuses of a transaction not in a closed block yet
+        "RECEIVED" | "PRE_CONFIRMED" | "CANDIDATE" => Some(TransactionStatus::Received),
+        "REJECTED" => Some(TransactionStatus::Rejected),
+        "ACCEPTED_ON_L2" => Some(TransactionStatus::AcceptedOnL2(execution)),
+        "ACCEPTED_ON_L1" => Some(TransactionStatus::AcceptedOnL1(execution)),
+        _ => None,
+    }
+}
+
+async fn poll_status(
+    provider: &JsonRpcClient<FailoverTransport>,
+    tx_hash: Felt,
+) -> Option<Result<(), Box<dyn std::error::Error>>> {
+    provider
+        .get_transaction_status(tx_hash)
+        .await
+        .ok()
+        .and_then(|status| final_status(tx_hash, status))
+}
+
+/// Waits for the declaration sent by one of the `declare_*_if_missing` functions, if any, and
+/// returns the class hash
+pub async fn confirm_declaration(
+    provider: &JsonRpcClient<FailoverTransport>,
+    ws: Option<&WsClient>,
+    name: &str,
+    (class_hash, tx_hash): (Felt, Option<Felt>),
+) -> Result<Felt, Box<dyn Error>> {
+    match tx_hash {
+        Some(tx_hash) => {
+            wait_for_confirmation(provider, ws, tx_hash).await?;
+            println!("{name} declared with class hash: 0x{:x}", class_hash);
+        }
+        None => println!(
+            "{name} already declared with class hash: 0x{:x}",
+            class_hash
+        ),
+    }
+    Ok(class_hash)
+}
+
+/// Waits for the transaction to be accepted, through a status subscription when a WebSocket
+/// client is given and by polling otherwise or if the subscription fails.
 pub async fn wait_for_confirmation(
-    provider: &JsonRpcClient<HttpTransport>,
+    provider: &JsonRpcClient<FailoverTransport>,
+    ws: Option<&WsClient>,
     tx_hash: Felt,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    loop {
-        match provider.get_transaction_status(tx_hash).await {
-            Ok(TransactionStatus::AcceptedOnL2(TransactionExecutionStatus::Succeeded))
-            | Ok(TransactionStatus::AcceptedOnL1(TransactionExecutionStatus::Succeeded)) => {
-                return Ok(());
-            }
-            Ok(TransactionStatus::AcceptedOnL2(TransactionExecutionStatus::Reverted))
-            | Ok(TransactionStatus::AcceptedOnL1(TransactionExecutionStatus::Reverted)) => {
-                return Err(format!("Transaction reverted").into());
+    if let Some(ws) = ws {
+        match ws.subscribe_transaction_status(tx_hash).await {
+            Ok(mut subscription) => {
+                // the transaction may have been accepted before the subscription
+                if let Some(outcome) = poll_status(provider, tx_hash).await {
+                    return outcome;
+                }
+                loop {
+                    match tokio::time::timeout(SUBSCRIPTION_TIMEOUT, subscription.next()).await {
+                        Ok(Some(notification)) => {
+                            if let Some(outcome) = parse_status_notification(&notification)
+                                .and_then(|status| final_status(tx_hash, status))
+                            {
+                                return outcome;
+                            }
+                        }
+                        // a missed notification shouldn't stall the bootstrap
+                        Err(_) => {
+                            if let Some(outcome) = poll_status(provider, tx_hash).await {
+                                return outcome;
+                            }
+                        }
+                        Ok(None) => {
+                            println!("WebSocket connection closed, polling instead");
+                            break;
+                        }
+                    }
+                }
             }
-            Ok(TransactionStatus::Rejected) => {
-                return Err(format!("Transaction rejected").into());
+            Err(e) => println!(
+                "Transaction status subscription failed ({}), polling instead",
+                e
+            ),
+        }
+    }
+
+    loop {
+        if let Some(outcome) = poll_status(provider, tx_hash).await {
+            return outcome;
+        }
+        println!("Waiting for transaction to be processed...");
+        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+    }
+}
+
+/// Waits for `block_number` to be closed and returns its hash and state root,
+/// woken up by new heads notifications when a WebSocket client is given.
+pub async fn wait_for_block(
+    provider: &JsonRpcClient<FailoverTransport>,
+    ws: Option<&WsClient>,
+    block_number: u64,
+) -> Result<(Felt, Felt), Box<dyn std::error::Error>> {
+    let mut subscription = match ws {
+        Some(ws) => match ws.subscribe_new_heads().await {
+            Ok(subscription) => Some(subscription),
+            Err(e) => {
+                println!("New heads subscription failed ({}), polling instead", e);
+                None
             }
-            Err(_) | Ok(TransactionStatus::Received) => {
-                println!("Waiting for transaction to be processed...");
-                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        },
+        None => None,
+    };
+
+    loop {
+        if let Ok(MaybePendingBlockWithTxHashes::Block(BlockWithTxHashes {
+            block_hash,
+            new_root,
+            ..
+        })) = provider
+            .get_block_with_tx_hashes(BlockId::Number(block_number))
+            .await
+        {
+            return Ok((block_hash, new_root));
+        }
+
+        println!("Waiting for block {} finalization...", block_number);
+        match &mut subscription {
+            Some(heads) => {
+                if let Ok(None) = tokio::time::timeout(SUBSCRIPTION_TIMEOUT, heads.next()).await {
+                    println!("WebSocket connection closed, polling instead");
+                    subscription = None;
+                }
             }
+            None => tokio::time::sleep(tokio::time::Duration::from_secs(5)).await,
         }
     }
 }
@@ -0,0 +1,283 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use starknet::core::types::Felt;
+use std::{
+    collections::HashMap,
+    error::Error,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::sync::{mpsc, oneshot};
+use tokio_tungstenite::tungstenite::{
+    client::IntoClientRequest,
+    http::{HeaderName, HeaderValue},
+    Message,
+};
+
+use crate::provider::{Auth, Endpoint};
+
+/// How long a subscription request may wait for its reply
+const SUBSCRIBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+type Reply = oneshot::Sender<Result<Value, String>>;
+
+struct PendingRequest {
+    reply: Reply,
+    /// Set for subscription requests, registered before the reply is delivered so that
+    /// notifications sent right after the subscription are not lost
+    notifications: Option<mpsc::UnboundedSender<Value>>,
+}
+
+#[derive(Default)]
+struct State {
+    pending: HashMap<u64, PendingRequest>,
+    subscriptions: HashMap<String, mpsc::UnboundedSender<Value>>,
+    /// Set by the reader once the connection is gone, nothing would answer new requests
+    closed: bool,
+}
+
+/// JSON-RPC client over a WebSocket, used for the RPC 0.8 `starknet_subscribe*` methods
+pub struct WsClient {
+    outgoing: mpsc::UnboundedSender<Message>,
+    state: Arc<Mutex<State>>,
+    next_id: AtomicU64,
+}
+
+/// Notifications of a subscription, unsubscribed when dropped
+pub struct Subscription {
+    id: Value,
+    notifications: mpsc::UnboundedReceiver<Value>,
+    outgoing: mpsc::UnboundedSender<Message>,
+}
+
+impl Subscription {
+    /// Next notification result, `None` once the connection is closed
+    pub async fn next(&mut self) -> Option<Value> {
+        self.notifications.recv().await
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        // fire and forget, the reply has no pending request and is ignored
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": "starknet_unsubscribe",
+            "params": { "subscription_id": self.id },
+            "id": u64::MAX,
+        });
+        let _ = self
+            .outgoing
+            .send(Message::Text(request.to_string().into()));
+    }
+}
+
+/// Key of a subscription id, which is a number in RPC 0.8.0 and a string from 0.8.1
+fn subscription_key(id: &Value) -> String {
+    match id {
+        Value::String(id) => id.clone(),
+        id => id.to_string(),
+    }
+}
+
+fn dispatch(state: &Mutex<State>, message: &str) {
+    let Ok(message) = serde_json::from_str::<Value>(message) else {
+        return;
+    };
+    let mut state = state.lock().unwrap();
+
+    if let Some(id) = message.get("id").and_then(Value::as_u64) {
+        let Some(request) = state.pending.remove(&id) else {
+            return;
+        };
+        let result = match (message.get("result"), message.get("error")) {
+            (Some(result), _) => Ok(result.clone()),
+            (None, Some(error)) => Err(error.to_string()),
+            (None, None) => Err(format!("invalid JSON-RPC response: {message}")),
+        };
+        if let (Ok(id), Some(notifications)) = (&result, request.notifications) {
+            state
+                .subscriptions
+                .insert(subscription_key(id), notifications);
+        }
+        let _ = request.reply.send(result);
+    } else if let Some(params) = message.get("params") {
+        let Some(id) = params.get("subscription_id") else {
+            return;
+        };
+        let key = subscription_key(id);
+        let closed = match state.subscriptions.get(&key) {
+            Some(notifications) => notifications
+                .send(params.get("result").cloned().unwrap_or(Value::Null))
+                .is_err(),
+            None => false,
+        };
+        if closed {
+            state.subscriptions.remove(&key);
+        }
+    }
+}
+
+impl WsClient {
+    pub async fn connect(endpoint: &Endpoint) -> Result<Self, Box<dyn Error>> {
+        let mut request = endpoint.url.as_str().into_client_request()?;
+        let headers = request.headers_mut();
+        for (name, value) in &endpoint.headers {
+            headers.insert(
+                HeaderName::try_from(name.as_str())?,
+                HeaderValue::try_from(value.as_str())?,
+            );
+        }
+        let authorization = match &endpoint.auth {
+            Some(Auth::Bearer(token)) => Some(format!("Bearer {token}")),
+            Some(Auth::Basic { username, password }) => Some(format!(
+                "Basic {}",
+                BASE64.encode(format!(
+                    "{username}:{}",
+                    password.as_deref().unwrap_or_default()
+                ))
+            )),
+            None => None,
+        };
+        if let Some(authorization) = authorization {
+            headers.insert("Authorization", HeaderValue::try_from(authorization)?);
+        }
+
+        let (stream, _) = tokio_tungstenite::connect_async(request).await?;
+        let (mut sink, mut source) = stream.split();
+        let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<Message>();
+        let state = Arc::new(Mutex::new(State::default()));
+
+        tokio::spawn(async move {
+            while let Some(message) = outgoing_rx.recv().await {
+                if sink.send(message).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let reader_state = state.clone();
+        let pong = outgoing.clone();
+        tokio::spawn(async move {
+            while let Some(Ok(message)) = source.next().await {
+                match message {
+                    Message::Text(text) => dispatch(&reader_state, &text),
+                    Message::Ping(payload) => {
+                        let _ = pong.send(Message::Pong(payload));
+                    }
+                    Message::Close(_) => break,
+                    _ => {}
+                }
+            }
+
+            // dropping the senders wakes up every pending request and subscription
+            let mut state = reader_state.lock().unwrap();
+            state.closed = true;
+            state.pending.clear();
+            state.subscriptions.clear();
+        });
+
+        Ok(Self {
+            outgoing,
+            state,
+            next_id: AtomicU64::new(0),
+        })
+    }
+
+    async fn request(
+        &self,
+        method: &str,
+        params: Value,
+        notifications: Option<mpsc::UnboundedSender<Value>>,
+    ) -> Result<Value, Box<dyn Error>> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (reply, response) = oneshot::channel();
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.closed {
+                return Err("WebSocket connection closed".into());
+            }
+            state.pending.insert(
+                id,
+                PendingRequest {
+                    reply,
+                    notifications,
+                },
+            );
+        }
+
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+            "id": id,
+        });
+        self.outgoing
+            .send(Message::Text(request.to_string().into()))
+            .map_err(|_| "WebSocket connection closed")?;
+
+        response
+            .await
+            .map_err(|_| "WebSocket connection closed")?
+            .map_err(|e| format!("{method} failed: {e}").into())
+    }
+
+    async fn subscribe(&self, method: &str, params: Value) -> Result<Subscription, Box<dyn Error>> {
+        let (sender, notifications) = mpsc::unbounded_channel();
+        let id = tokio::time::timeout(
+            SUBSCRIBE_TIMEOUT,
+            self.request(method, params, Some(sender)),
+        )
+        .await
+        .map_err(|_| format!("{method} timed out"))??;
+
+        Ok(Subscription {
+            id,
+            notifications,
+            outgoing: self.outgoing.clone(),
+        })
+    }
+
+    /// Notifications are `{ transaction_hash, status: { finality_status, execution_status, .. } }`
+    pub async fn subscribe_transaction_status(
+        &self,
+        tx_hash: Felt,
+    ) -> Result<Subscription, Box<dyn Error>> {
+        self.subscribe(
+            "starknet_subscribeTransactionStatus",
+            json!({ "transaction_hash": format!("0x{:x}", tx_hash) }),
+        )
+        .await
+    }
+
+    /// Notifications are block headers
+    pub async fn subscribe_new_heads(&self) -> Result<Subscription, Box<dyn Error>> {
+        self.subscribe("starknet_subscribeNewHeads", json!({}))
+            .await
+    }
+}
+
+/// Connects to the WebSocket endpoint if there is one, a failure falls back to polling
+pub async fn connect_or_poll(endpoint: Option<&Endpoint>) -> Option<WsClient> {
+    let endpoint = endpoint?;
+    match WsClient::connect(endpoint).await {
+        Ok(client) => {
+            println!(
+                "Connected to {} for transaction status subscriptions",
+                endpoint.url
+            );
+            Some(client)
+        }
+        Err(e) => {
+            println!(
+                "WebSocket connection to {} failed ({}), polling instead",
+                endpoint.url, e
+            );
+            None
+        }
+    }
+}